@@ -1,11 +1,14 @@
-use anyhow::Result;
-use clap::{CommandFactory, Parser};
-use common::{cli, ipc};
-use std::os::unix::net::UnixStream;
-use tracing::{debug, error, info};
+use anyhow::{Result, anyhow};
+use clap::{CommandFactory, Parser, ValueEnum};
+use common::{cli, config, history, ipc, palette};
+use std::{io::Write, os::unix::net::UnixStream};
+use tracing::{debug, info, warn};
 
 fn main() -> Result<()> {
-    tracing_subscriber::fmt::init();
+    // Stdout is kept for the output of the commands, e.g. the PNG of `pwww screenshot`.
+    tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .init();
     let args = cli::client::Args::parse();
 
     if let cli::client::ClientSubcommand::Completion { shell } = args.subcommand {
@@ -16,12 +19,27 @@ fn main() -> Result<()> {
         return Ok(());
     }
 
-    let message = ipc::Message::from_cli_command(args.subcommand);
+    let json = matches!(
+        args.subcommand,
+        cli::client::ClientSubcommand::Outputs { json: true }
+            | cli::client::ClientSubcommand::History { json: true }
+            | cli::client::ClientSubcommand::Colors { json: true, .. }
+    );
+    let message = ipc::Message::from_cli_command(args.subcommand)?;
 
     debug!("Message to be sent: {message:?}");
 
     debug!("Trying to connect pwwwd socked ...");
-    let uds_path = ipc::default_uds_path()?;
+    // The config is shared with the daemon, so an invalid option must not keep the client from
+    // connecting, e.g. to reload the fixed config.
+    let uds_path = match config::Config::load() {
+        Ok(config) => config.socket_path()?,
+        Err(e) => {
+            warn!("{e}");
+            warn!("Falling back to the default socket");
+            ipc::default_uds_path()?
+        }
+    };
     let mut socket = UnixStream::connect(uds_path)?;
 
     debug!("Trying to send the message to the daemon ...");
@@ -32,8 +50,146 @@ fn main() -> Result<()> {
 
     match reply {
         ipc::Reply::Ok => info!("Ok"),
-        ipc::Reply::Error(e) => error!("Daemon encountered error when processing the request: {e}"),
+        ipc::Reply::Error(e) => {
+            return Err(anyhow!(
+                "Daemon encountered error when processing the request: {e}"
+            ));
+        }
+        ipc::Reply::Outputs(outputs) if json => {
+            println!("{}", serde_json::to_string_pretty(&outputs)?)
+        }
+        ipc::Reply::Outputs(outputs) => print_outputs(&outputs),
+        ipc::Reply::History(history) if json => {
+            println!("{}", serde_json::to_string_pretty(&history)?)
+        }
+        ipc::Reply::History(history) => print_history(&history),
+        ipc::Reply::Colors(palette) if json => {
+            println!("{}", serde_json::to_string_pretty(&palette)?)
+        }
+        ipc::Reply::Colors(palette) => print_palette(&palette),
+        ipc::Reply::Screenshots(screenshots) => save_screenshots(screenshots)?,
+        ipc::Reply::Reloaded(not_applied) => {
+            info!("Ok");
+            for option in not_applied {
+                warn!("Not applied until pwwwd restarts: {option}");
+            }
+        }
     }
 
     Ok(())
 }
+
+/// One wallpaper per line, with the displayed one marked by `*`.
+fn print_history(history: &history::History) {
+    for (i, args) in history.entries.iter().enumerate() {
+        let displayed = i == history.position && !history.unrecorded;
+        let mark = if displayed { '*' } else { ' ' };
+        let outputs = if args.outputs.is_empty() {
+            String::new()
+        } else {
+            format!(" ({})", args.outputs.join(", "))
+        };
+        println!("{mark} {i:>3}  {}{outputs}", args.image.summary());
+    }
+}
+
+/// The dominant colors with their proportions, then the other colors of the palette.
+fn print_palette(palette: &palette::Palette) {
+    for color in &palette.colors {
+        println!(
+            "{}  {:>5.1}%",
+            palette::hex(color.rgb),
+            color.proportion * 100.0
+        );
+    }
+    println!();
+    println!("average     {}", palette::hex(palette.average));
+    println!("edge        {}", palette::hex(palette.edge));
+    println!("background  {}", palette::hex(palette.background));
+    println!("foreground  {}", palette::hex(palette.foreground));
+    println!("luminance   {:.3}", palette.luminance);
+}
+
+/// Print where the screenshots are written by the daemon, or write the PNG sent back to stdout.
+fn save_screenshots(screenshots: Vec<ipc::Screenshot>) -> Result<()> {
+    if screenshots.is_empty() {
+        warn!("No output is displaying a wallpaper");
+    }
+
+    // PNGs are only sent back for a single output.
+    for screenshot in screenshots {
+        match (screenshot.path, screenshot.png) {
+            (Some(path), _) => println!("{}", path.display()),
+            (None, Some(png)) => std::io::stdout().write_all(&png)?,
+            (None, None) => {}
+        }
+    }
+
+    Ok(())
+}
+
+fn print_outputs(outputs: &[ipc::OutputInfo]) {
+    const HEADER: [&str; 10] = [
+        "NAME",
+        "DESCRIPTION",
+        "MAKE/MODEL",
+        "POSITION",
+        "SIZE",
+        "MODE",
+        "SCALE",
+        "TRANSFORM",
+        "RESIZE",
+        "WALLPAPER",
+    ];
+
+    let or_dash = |s: Option<String>| s.unwrap_or_else(|| "-".to_string());
+    let rows: Vec<[String; 10]> = outputs
+        .iter()
+        .map(|output| {
+            [
+                or_dash(output.name.clone()),
+                or_dash(output.description.clone()),
+                format!("{} {}", output.make, output.model),
+                or_dash(output.logical_position.map(|(x, y)| format!("{x},{y}"))),
+                or_dash(output.logical_size.map(|(w, h)| format!("{w}x{h}"))),
+                or_dash(output.mode.map(|mode| {
+                    format!(
+                        "{}x{}@{:.2}Hz",
+                        mode.width,
+                        mode.height,
+                        mode.refresh as f64 / 1000.0
+                    )
+                })),
+                format!("{}", output.scale),
+                output.transform.clone(),
+                or_dash(output.resize.and_then(|resize| {
+                    resize
+                        .to_possible_value()
+                        .map(|value| value.get_name().to_string())
+                })),
+                or_dash(output.wallpaper.clone()),
+            ]
+        })
+        .collect();
+
+    let mut widths = HEADER.map(str::len);
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let print_row = |cells: Vec<&str>| {
+        let line = cells
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{cell:<width$}"))
+            .collect::<Vec<_>>()
+            .join("  ");
+        println!("{}", line.trim_end());
+    };
+    print_row(HEADER.to_vec());
+    for row in &rows {
+        print_row(row.iter().map(String::as_str).collect());
+    }
+}
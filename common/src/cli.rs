@@ -5,7 +5,30 @@ fn canonicalize_path(s: &str) -> Result<std::path::PathBuf, String> {
     std::fs::canonicalize(s).map_err(|e| format!("{}: {}", s, e))
 }
 
-fn parse_rgb(s: &str) -> Result<(u8, u8, u8)> {
+/// Path used on the command line to read encoded image data from stdin.
+pub const STDIN_PATH: &str = "-";
+
+fn canonicalize_image_path(s: &str) -> Result<std::path::PathBuf, String> {
+    if s == STDIN_PATH {
+        return Ok(std::path::PathBuf::from(STDIN_PATH));
+    }
+
+    canonicalize_path(s)
+}
+
+/// Path used on the command line to write data to stdout.
+pub const STDOUT_PATH: &str = "-";
+
+/// Paths to be written by the daemon, which may not exist yet.
+fn absolute_output_path(s: &str) -> Result<std::path::PathBuf, String> {
+    if s == STDOUT_PATH {
+        return Ok(std::path::PathBuf::from(STDOUT_PATH));
+    }
+
+    std::path::absolute(s).map_err(|e| format!("{}: {}", s, e))
+}
+
+pub(crate) fn parse_rgb(s: &str) -> Result<(u8, u8, u8)> {
     if s.len() != 6 {
         return Err(anyhow!("RGBA must have 8 hex chars"));
     }
@@ -16,8 +39,21 @@ fn parse_rgb(s: &str) -> Result<(u8, u8, u8)> {
     Ok((r, g, b))
 }
 
+fn parse_offset(s: &str) -> Result<(f64, f64)> {
+    let (x, y) = s
+        .split_once(',')
+        .ok_or(anyhow!("Offset must be in the form of `<X>,<Y>`"))?;
+
+    Ok((
+        x.parse::<f64>()
+            .map_err(|e| anyhow!("Failed to parse offset: {e}"))?,
+        y.parse::<f64>()
+            .map_err(|e| anyhow!("Failed to parse offset: {e}"))?,
+    ))
+}
+
 pub mod server {
-    use crate::cli::parse_rgb;
+    use crate::cli::{parse_offset, parse_rgb};
     use anyhow::{Result, anyhow};
     use clap_complete::Shell;
     use directories::BaseDirs;
@@ -28,12 +64,18 @@ pub mod server {
     #[command(version = "0.1.0")]
     #[command(about = "Phillips's wgpu-based Wayland wallpaper daemon")]
     pub struct Args {
-        /// Which image to load as the first wallpaper since startup
+        /// Which image to load as the first wallpaper since startup. Without it, the screen is
+        /// filled with the fill color until a wallpaper is set by the client
         #[command(subcommand)]
-        pub subcommand: ServerSubcommand,
+        pub subcommand: Option<ServerSubcommand>,
+
+        /// Where the compositor places the wallpaper
+        #[command(flatten)]
+        pub surface: SurfaceOptions,
     }
 
     #[derive(clap::Subcommand)]
+    #[allow(clippy::large_enum_variant)]
     pub enum ServerSubcommand {
         /// Load image from specified path
         #[command(name = "load")]
@@ -45,10 +87,52 @@ pub mod server {
             #[command(flatten)]
             resize: Resize,
 
+            /// How to tile the image with `--resize tile`
+            #[command(flatten)]
+            tile: TileOptions,
+
+            /// Where to place the resized image on the screen
+            #[command(flatten)]
+            placement: Placement,
+
+            /// Which part of the image to display, and how to orient it
+            #[command(flatten)]
+            source_transform: SourceTransform,
+
             /// Which color to fill the padding with when loaded image does not fill the screen
             #[arg(long ,short, value_parser = parse_rgb)]
             fill_rgb: Option<(u8, u8, u8)>,
+
+            /// How to fill the padding when loaded image does not fill the screen
+            #[command(flatten)]
+            padding: Padding,
+
+            /// Set the type of transition from the fill color
+            #[command(flatten)]
+            transition: Transition,
+
+            /// Set the options of transition
+            #[command(flatten)]
+            transition_options: TransitionOptions,
+
+            /// Set the options for easing function of transition
+            #[command(flatten)]
+            ease: Ease,
+        },
+
+        /// Fill the screen with a solid color
+        Color {
+            /// The color in the form of `RRGGBB`
+            #[arg(value_parser = parse_rgb)]
+            rgb: (u8, u8, u8),
+        },
+
+        /// Fill the screen with a gradient
+        Gradient {
+            #[command(flatten)]
+            gradient: GradientArgs,
         },
+
         /// Restore last used image
         Restore,
 
@@ -59,11 +143,10 @@ pub mod server {
         },
     }
 
-    /// Get the restore file path. Create parent directory if it doesn't exist.
-    pub fn default_restore_path() -> Result<PathBuf> {
-        let dirs = BaseDirs::new().ok_or(anyhow!(
-            "Cannot create `BaseDirs` to get default restore path"
-        ))?;
+    /// Get the state directory of the daemon. Create it if it doesn't exist.
+    pub fn default_state_dir() -> Result<PathBuf> {
+        let dirs =
+            BaseDirs::new().ok_or(anyhow!("Cannot create `BaseDirs` to get default state dir"))?;
 
         let dir = dirs
             .state_dir()
@@ -74,11 +157,39 @@ pub mod server {
             std::fs::create_dir_all(&dir)?;
         }
 
-        let restore_file = dir.join("restore-path").to_owned();
+        Ok(dir)
+    }
+
+    /// Get the restore file path. Create parent directory if it doesn't exist.
+    pub fn default_restore_path() -> Result<PathBuf> {
+        let restore_file = default_state_dir()?.join("restore.json");
+        Ok(restore_file)
+    }
 
+    /// Get the path of the msgpack restore file written by older versions, which is migrated
+    /// when there is no restore file yet.
+    pub fn default_legacy_restore_path() -> Result<PathBuf> {
+        let restore_file = default_state_dir()?.join("restore-path");
         Ok(restore_file)
     }
 
+    /// Get the path of the wallpaper history. Create parent directory if it doesn't exist.
+    pub fn default_history_path() -> Result<PathBuf> {
+        let history_file = default_state_dir()?.join("history.json");
+        Ok(history_file)
+    }
+
+    /// Get the path where image data sent through the socket is kept, so that it can be
+    /// restored later. Outputs with a wallpaper of their own have their own file. Create parent
+    /// directory if it doesn't exist.
+    pub fn default_restore_image_path(output: Option<&str>) -> Result<PathBuf> {
+        let image_file = match output {
+            Some(output) => default_state_dir()?.join(format!("restore-image-{output}")),
+            None => default_state_dir()?.join("restore-image"),
+        };
+        Ok(image_file)
+    }
+
     pub const RGB: (u8, u8, u8) = (0x22, 0x44, 0x66);
 
     #[derive(clap::Args)]
@@ -93,6 +204,17 @@ pub mod server {
         pub resize: Option<ResizeOption>,
     }
 
+    impl Resize {
+        /// `None` if the default resize option should be used.
+        pub fn into_option(self) -> Option<ResizeOption> {
+            if self.no_resize {
+                Some(ResizeOption::No)
+            } else {
+                self.resize
+            }
+        }
+    }
+
     #[derive(
         Copy, Clone, clap::ValueEnum, Debug, serde::Serialize, serde::Deserialize, PartialEq, Eq,
     )]
@@ -107,20 +229,287 @@ pub mod server {
         /// Stretch the image to fit inside the screen, without preserving the original aspect
         /// ratio
         Stretch,
+        /// Repeat the image across the whole screen. Can be controlled by `--tile-scale <SCALE>`,
+        /// `--tile-offset <X,Y>` and `--tile-mirror`
+        Tile,
+        /// Scale the image by the largest whole-number factor that fits inside the screen, with
+        /// nearest filtering. Suitable for pixel art
+        Integer,
+        /// Scale the image by the smallest whole-number factor that fills the entire screen, with
+        /// nearest filtering, cropping out parts that don't fit. Suitable for pixel art
+        IntegerCrop,
+    }
+
+    #[derive(
+        Copy, Clone, clap::Args, serde::Serialize, serde::Deserialize, Debug, Default, PartialEq,
+    )]
+    pub struct TileOptions {
+        /// Scale of each tile relative to the native image size. Default: 1.0
+        #[arg(long = "tile-scale", value_parser = parse_tile_scale)]
+        pub scale: Option<f64>,
+
+        /// Offset of the tiles from the top-left corner of the screen in pixels. Default: 0,0
+        #[arg(
+            long = "tile-offset",
+            value_parser = parse_offset,
+            allow_hyphen_values = true
+        )]
+        pub offset: Option<(f64, f64)>,
+
+        /// Mirror every other tile, so that the edges of neighbouring tiles match
+        #[arg(long = "tile-mirror")]
+        pub mirror: bool,
+    }
+
+    pub const DEFAULT_TILE_SCALE: f64 = 1.0;
+    pub const DEFAULT_TILE_OFFSET: (f64, f64) = (0.0, 0.0);
+
+    #[derive(
+        Copy, Clone, clap::Args, serde::Serialize, serde::Deserialize, Debug, Default, PartialEq,
+    )]
+    pub struct Placement {
+        /// Which edge or corner of the screen to align the image to. Default: center
+        #[arg(long = "align", conflicts_with = "focal_point")]
+        pub align: Option<Align>,
+
+        /// Point of the image in normalized image coordinates, i.e. `0,0` is the top-left corner
+        /// and `1,1` is the bottom-right corner, which is kept as close to the center of the
+        /// screen as possible
+        #[arg(long = "focal-point", value_parser = parse_focal_point)]
+        pub focal_point: Option<(f64, f64)>,
+
+        /// Zoom the resized image in or out around its center. Default: 1.0
+        #[arg(long = "zoom", value_parser = parse_zoom)]
+        pub zoom: Option<f64>,
+
+        /// Move the image by `<X>,<Y>` pixels after aligning and zooming it. Default: 0,0
+        #[arg(long = "pan", value_parser = parse_offset, allow_hyphen_values = true)]
+        pub pan: Option<(f64, f64)>,
+    }
+
+    #[derive(
+        Copy,
+        Clone,
+        clap::ValueEnum,
+        Debug,
+        serde::Serialize,
+        serde::Deserialize,
+        PartialEq,
+        Eq,
+        Default,
+    )]
+    pub enum Align {
+        #[default]
+        Center,
+        Top,
+        Bottom,
+        Left,
+        Right,
+        TopLeft,
+        TopRight,
+        BottomLeft,
+        BottomRight,
+    }
+
+    #[derive(
+        Copy, Clone, clap::Args, serde::Serialize, serde::Deserialize, Debug, Default, PartialEq,
+    )]
+    pub struct SourceTransform {
+        /// Only display the rectangle of `<W>x<H>` pixels at `<X>,<Y>` of the image, in the form
+        /// of `<X>,<Y>,<W>,<H>`. Ignored with `--resize tile`
+        #[arg(long = "crop", value_parser = parse_crop)]
+        pub crop: Option<(u32, u32, u32, u32)>,
+
+        /// Rotate the image clockwise, after cropping it
+        #[arg(long = "rotate")]
+        pub rotate: Option<Rotation>,
+
+        /// Flip the image, after rotating it
+        #[arg(long = "flip")]
+        pub flip: Option<Flip>,
+    }
+
+    #[derive(
+        Copy, Clone, clap::ValueEnum, Debug, serde::Serialize, serde::Deserialize, PartialEq, Eq,
+    )]
+    pub enum Rotation {
+        #[value(name = "90")]
+        Rotate90,
+        #[value(name = "180")]
+        Rotate180,
+        #[value(name = "270")]
+        Rotate270,
+    }
+
+    #[derive(
+        Copy, Clone, clap::ValueEnum, Debug, serde::Serialize, serde::Deserialize, PartialEq, Eq,
+    )]
+    pub enum Flip {
+        /// Flip left and right
+        #[value(name = "h", alias = "horizontal")]
+        Horizontal,
+        /// Flip top and bottom
+        #[value(name = "v", alias = "vertical")]
+        Vertical,
+    }
+
+    fn parse_crop(s: &str) -> Result<(u32, u32, u32, u32)> {
+        let parts = s
+            .split(',')
+            .map(|part| {
+                part.trim()
+                    .parse::<u32>()
+                    .map_err(|e| anyhow!("Failed to parse crop rectangle: {e}"))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let [x, y, w, h] = parts[..] else {
+            return Err(anyhow!(
+                "Crop rectangle must be in the form of `<X>,<Y>,<W>,<H>`"
+            ));
+        };
+        if w == 0 || h == 0 {
+            return Err(anyhow!("Crop rectangle must not be empty"));
+        }
+
+        Ok((x, y, w, h))
+    }
+
+    pub const DEFAULT_ALIGN: Align = Align::Center;
+    pub const DEFAULT_ZOOM: f64 = 1.0;
+    pub const DEFAULT_PAN: (f64, f64) = (0.0, 0.0);
+
+    fn parse_focal_point(s: &str) -> Result<(f64, f64)> {
+        let (x, y) = parse_offset(s)?;
+        if !(0.0..=1.0).contains(&x) || !(0.0..=1.0).contains(&y) {
+            return Err(anyhow!("Focal point must be in range [0.0, 1.0]"));
+        }
+
+        Ok((x, y))
+    }
+
+    fn parse_zoom(s: &str) -> Result<f64> {
+        let zoom = s
+            .parse::<f64>()
+            .map_err(|e| anyhow!("Failed to parse zoom: {e}"))?;
+        if zoom <= 0.0 {
+            return Err(anyhow!("Zoom must be positive"));
+        }
+
+        Ok(zoom)
+    }
+
+    fn parse_tile_scale(s: &str) -> Result<f64> {
+        let scale = s
+            .parse::<f64>()
+            .map_err(|e| anyhow!("Failed to parse tile scale: {e}"))?;
+        if !scale.is_finite() || scale <= 0.0 {
+            return Err(anyhow!("Tile scale must be positive"));
+        }
+
+        Ok(scale)
     }
 
     pub const DEFAULT_RESIZE: ResizeOption = ResizeOption::Crop;
 
-    pub use super::client::{DEFAULT_TRANSITION_DURATION, DEFAULT_TRANSITION_FPS};
+    #[derive(clap::Args, Clone, Debug, Default)]
+    pub struct SurfaceOptions {
+        /// Which layer to put the wallpaper on. Default: background
+        #[arg(long, global = true)]
+        pub layer: Option<SurfaceLayer>,
+
+        /// Which edges of the output to anchor the wallpaper to, e.g. `bottom,left`. Default: all
+        /// edges
+        #[arg(long, global = true, value_delimiter = ',')]
+        pub anchor: Option<Vec<Edge>>,
+
+        /// Margins from the anchored edges in the form of `<TOP>,<RIGHT>,<BOTTOM>,<LEFT>`.
+        /// Default: 0,0,0,0
+        #[arg(
+            long,
+            global = true,
+            value_parser = parse_margin,
+            allow_hyphen_values = true
+        )]
+        pub margin: Option<(i32, i32, i32, i32)>,
+
+        /// Size of the wallpaper in the form of `<WIDTH>x<HEIGHT>`. `0` lets the compositor decide
+        /// the size along an axis, which must be anchored to both edges then. Default: 0x0
+        #[arg(long, global = true, value_parser = parse_size)]
+        pub size: Option<(u32, u32)>,
+
+        /// Space reserved for the wallpaper, which other surfaces won't cover. `0` moves the
+        /// wallpaper to leave room for panels, and `-1` extends it under them. Default: -1
+        #[arg(long, global = true, allow_hyphen_values = true)]
+        pub exclusive_zone: Option<i32>,
+    }
+
+    #[derive(Copy, Clone, clap::ValueEnum, Debug, Default, PartialEq, Eq)]
+    pub enum SurfaceLayer {
+        /// Below everything. Default layer
+        #[default]
+        Background,
+        /// Above the background, but below windows
+        Bottom,
+    }
+
+    #[derive(Copy, Clone, clap::ValueEnum, Debug, PartialEq, Eq)]
+    pub enum Edge {
+        Top,
+        Bottom,
+        Left,
+        Right,
+    }
+
+    fn parse_margin(s: &str) -> Result<(i32, i32, i32, i32)> {
+        let parts = s
+            .split(',')
+            .map(|part| {
+                part.trim()
+                    .parse::<i32>()
+                    .map_err(|e| anyhow!("Failed to parse margin: {e}"))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let [top, right, bottom, left] = parts[..] else {
+            return Err(anyhow!(
+                "Margin must be in the form of `<TOP>,<RIGHT>,<BOTTOM>,<LEFT>`"
+            ));
+        };
+
+        Ok((top, right, bottom, left))
+    }
+
+    fn parse_size(s: &str) -> Result<(u32, u32)> {
+        let (width, height) = s
+            .split_once('x')
+            .ok_or(anyhow!("Size must be in the form of `<WIDTH>x<HEIGHT>`"))?;
+
+        Ok((
+            width
+                .parse::<u32>()
+                .map_err(|e| anyhow!("Failed to parse size: {e}"))?,
+            height
+                .parse::<u32>()
+                .map_err(|e| anyhow!("Failed to parse size: {e}"))?,
+        ))
+    }
+
+    pub use super::client::{
+        DEFAULT_TRANSITION_DURATION, DEFAULT_TRANSITION_FPS, Ease, GradientArgs, Padding,
+        Transition, TransitionOptions,
+    };
 }
 
 pub mod client {
     use crate::cli::parse_rgb;
     use anyhow::{Result, anyhow};
     use clap_complete::Shell;
-    use std::path::PathBuf;
+    use std::{path::PathBuf, time::Duration};
 
-    pub use super::server::{Resize, ResizeOption};
+    pub use super::server::{
+        Align, Flip, Placement, Resize, ResizeOption, Rotation, SourceTransform, TileOptions,
+    };
 
     #[derive(clap::Parser)]
     #[command(name = "pwww")]
@@ -136,14 +525,32 @@ pub mod client {
         #[command(name = "img")]
         /// Send a new image path for pwwwd to display
         SwitchImage {
-            /// The path of the new image
-            #[arg(value_parser = super::canonicalize_path)]
+            /// The path of the new image. Use `-` to read encoded image data from stdin and send
+            /// it to the daemon through the socket
+            #[arg(value_parser = super::canonicalize_image_path)]
             image: PathBuf,
 
+            /// Only change the wallpaper of this output, e.g. `DP-1`. Can be given multiple times.
+            /// Default: all outputs
+            #[arg(long = "output", short = 'o')]
+            outputs: Vec<String>,
+
             /// How to resize the image
             #[command(flatten)]
             resize: Resize,
 
+            /// How to tile the image with `--resize tile`
+            #[command(flatten)]
+            tile: TileOptions,
+
+            /// Where to place the resized image on the screen
+            #[command(flatten)]
+            placement: Placement,
+
+            /// Which part of the image to display, and how to orient it
+            #[command(flatten)]
+            source_transform: SourceTransform,
+
             /// Set the type of transition
             #[command(flatten)]
             transition: Transition,
@@ -159,8 +566,123 @@ pub mod client {
             /// Which color to fill the padding with when loaded image does not fill the screen
             #[arg(long ,short, value_parser = parse_rgb)]
             fill_rgb: Option<(u8, u8, u8)>,
+
+            /// How to fill the padding when loaded image does not fill the screen
+            #[command(flatten)]
+            padding: Padding,
+        },
+
+        /// Fill the screen with a solid color
+        Color {
+            /// The color in the form of `RRGGBB`
+            #[arg(value_parser = parse_rgb)]
+            rgb: (u8, u8, u8),
+
+            /// Only change the wallpaper of this output, e.g. `DP-1`. Can be given multiple times.
+            /// Default: all outputs
+            #[arg(long = "output", short = 'o')]
+            outputs: Vec<String>,
+
+            /// Set the type of transition
+            #[command(flatten)]
+            transition: Transition,
+
+            /// Set the options of transition
+            #[command(flatten)]
+            transition_options: TransitionOptions,
+
+            /// Set the options for easing function of transition
+            #[command(flatten)]
+            ease: Ease,
+        },
+
+        /// Fill the screen with a gradient
+        Gradient {
+            #[command(flatten)]
+            gradient: GradientArgs,
+
+            /// Only change the wallpaper of this output, e.g. `DP-1`. Can be given multiple times.
+            /// Default: all outputs
+            #[arg(long = "output", short = 'o')]
+            outputs: Vec<String>,
+
+            /// Set the type of transition
+            #[command(flatten)]
+            transition: Transition,
+
+            /// Set the options of transition
+            #[command(flatten)]
+            transition_options: TransitionOptions,
+
+            /// Set the options for easing function of transition
+            #[command(flatten)]
+            ease: Ease,
+        },
+
+        /// Cycle through the images in directories, or the given images, on a timer. Use
+        /// `pwww slideshow pause|resume|next|prev|stop` to control the running slideshow
+        #[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+        Slideshow {
+            #[command(subcommand)]
+            command: Option<SlideshowCommand>,
+
+            #[command(flatten)]
+            args: SlideshowArgs,
+        },
+
+        /// Control the running transitions of all outputs, even while they are running
+        Transition {
+            #[command(subcommand)]
+            command: TransitionCommand,
+        },
+
+        /// Go back to the previous wallpaper in the history, with the configured transition
+        Prev,
+
+        /// Go forward to the next wallpaper in the history, with the configured transition
+        Next,
+
+        /// List the wallpapers in the history, oldest first
+        History {
+            /// Print the history as JSON instead of a list
+            #[arg(long)]
+            json: bool,
+        },
+
+        /// Print the palette extracted from the wallpaper
+        Colors {
+            /// The wallpaper of this output, e.g. `DP-1`. Default: the wallpaper of all outputs
+            #[arg(long, short)]
+            output: Option<String>,
+
+            /// Print the palette as JSON instead of a list
+            #[arg(long)]
+            json: bool,
+        },
+
+        /// Save what is displayed as PNG, after resizing and padding, and in the middle of
+        /// transitions
+        Screenshot {
+            /// Capture this output, e.g. `DP-1`. Default: all outputs
+            #[arg(long, short)]
+            output: Option<String>,
+
+            /// The PNG file to save to, or the directory to save `<OUTPUT>.png` of each output to
+            /// if `--output` isn't given. `-` writes the PNG of `--output` to stdout
+            #[arg(default_value = super::STDOUT_PATH, value_parser = super::absolute_output_path)]
+            path: std::path::PathBuf,
+        },
+
+        /// List the outputs, and the wallpaper shown on each of them
+        Outputs {
+            /// Print the outputs as JSON instead of a table
+            #[arg(long)]
+            json: bool,
         },
 
+        /// Re-read the config file of pwwwd, and apply it without restarting
+        Reload,
+
         /// Kill pwwwd daemon
         Kill,
 
@@ -183,6 +705,17 @@ pub mod client {
         pub transition: Option<TransitionKind>,
     }
 
+    impl Transition {
+        /// `None` if the default transition should be used.
+        pub fn into_option(self) -> Option<TransitionKind> {
+            if self.no_transition {
+                Some(TransitionKind::No)
+            } else {
+                self.transition
+            }
+        }
+    }
+
     #[derive(
         Copy, Clone, clap::ValueEnum, serde::Serialize, serde::Deserialize, Debug, PartialEq, Eq,
     )]
@@ -212,6 +745,19 @@ pub mod client {
         pub cubic_curve: Option<(f64, f64, f64, f64)>,
     }
 
+    impl Ease {
+        /// `None` if the default easing function should be used.
+        pub fn into_option(self) -> Option<EaseKind> {
+            if self.no_ease {
+                Some(EaseKind::No)
+            } else if let Some((px1, py1, px2, py2)) = self.cubic_curve {
+                Some(EaseKind::CubicBezier(px1, py1, px2, py2))
+            } else {
+                self.ease
+            }
+        }
+    }
+
     pub fn parse_cubic_bezier_control_points(s: &str) -> Result<(f64, f64, f64, f64)> {
         let point_str: Vec<&str> = s.split(",").collect();
 
@@ -237,7 +783,9 @@ pub mod client {
         ))
     }
 
-    #[derive(Copy, Clone, clap::ValueEnum, serde::Serialize, serde::Deserialize, Debug)]
+    #[derive(
+        Copy, Clone, clap::ValueEnum, serde::Serialize, serde::Deserialize, Debug, PartialEq,
+    )]
     pub enum EaseKind {
         /// Default
         No,
@@ -272,7 +820,9 @@ pub mod client {
         EaseInOutCirc,
     }
 
-    #[derive(Copy, Clone, clap::Args, serde::Serialize, serde::Deserialize, Debug)]
+    #[derive(
+        Copy, Clone, clap::Args, serde::Serialize, serde::Deserialize, Debug, Default, PartialEq,
+    )]
     pub struct TransitionOptions {
         /// How long the transition will take in seconds. Default: 3
         #[arg(long, name = "transition-duration")]
@@ -291,9 +841,375 @@ pub mod client {
         pub wipe_angle: Option<f64>,
     }
 
+    #[derive(clap::Args)]
+    pub struct GradientArgs {
+        /// Color stops in the form of `RRGGBB[@OFFSET]`, at most 16. Offsets are within `[0, 1]`.
+        /// Stops without offsets are spread evenly between their neighbours
+        #[arg(required = true, num_args = 1.., value_parser = parse_gradient_stop)]
+        pub stops: Vec<GradientStop>,
+
+        /// Shape of the gradient. Default: linear
+        #[arg(long = "gradient-kind")]
+        pub kind: Option<GradientKind>,
+
+        /// Direction of linear gradients in degrees, counter-clockwise from left to right.
+        /// Default: 0.0
+        #[arg(long = "gradient-angle", allow_hyphen_values = true)]
+        pub angle: Option<f64>,
+    }
+
+    impl GradientArgs {
+        pub fn into_gradient(self) -> Result<Gradient> {
+            if self.stops.len() > MAX_GRADIENT_STOPS {
+                return Err(anyhow!(
+                    "A gradient can have at most {MAX_GRADIENT_STOPS} stops, but {} are given",
+                    self.stops.len()
+                ));
+            }
+
+            Ok(Gradient {
+                kind: self.kind.unwrap_or(DEFAULT_GRADIENT_KIND),
+                stops: self.stops,
+                angle: self.angle.unwrap_or(DEFAULT_GRADIENT_ANGLE),
+            })
+        }
+    }
+
+    #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, PartialEq)]
+    pub struct Gradient {
+        pub kind: GradientKind,
+        pub stops: Vec<GradientStop>,
+        pub angle: f64,
+    }
+
+    impl Gradient {
+        /// A gradient with only one stop, which is rendered as a solid color.
+        pub fn solid(rgb: (u8, u8, u8)) -> Self {
+            Self {
+                kind: DEFAULT_GRADIENT_KIND,
+                stops: vec![GradientStop { rgb, offset: None }],
+                angle: DEFAULT_GRADIENT_ANGLE,
+            }
+        }
+    }
+
+    #[derive(
+        Copy, Clone, clap::ValueEnum, serde::Serialize, serde::Deserialize, Debug, PartialEq, Eq,
+    )]
+    pub enum GradientKind {
+        /// Colors change along a straight line. Can be controlled by `--gradient-angle <ANGLE>`
+        Linear,
+        /// Colors change from the center of the screen to its corners
+        Radial,
+    }
+
+    #[derive(Copy, Clone, Debug, serde::Serialize, serde::Deserialize, PartialEq)]
+    pub struct GradientStop {
+        pub rgb: (u8, u8, u8),
+        pub offset: Option<f64>,
+    }
+
+    #[derive(clap::Args)]
+    pub struct SlideshowArgs {
+        /// Directories to pick images from, or images
+        #[arg(required = true, value_parser = super::canonicalize_path)]
+        pub paths: Vec<PathBuf>,
+
+        /// How long each image is displayed, e.g. `90s`, `10m` or `1h 30m`. Default: 10m
+        #[arg(long, value_parser = parse_interval)]
+        pub interval: Option<Duration>,
+
+        /// In which order the images are displayed. Default: name
+        #[arg(long)]
+        pub order: Option<SlideshowOrder>,
+
+        /// Also pick images in the subdirectories
+        #[arg(long, short)]
+        pub recursive: bool,
+
+        /// How to resize the images
+        #[command(flatten)]
+        pub resize: Resize,
+
+        /// How to tile the images with `--resize tile`
+        #[command(flatten)]
+        pub tile: TileOptions,
+
+        /// Where to place the resized images on the screen
+        #[command(flatten)]
+        pub placement: Placement,
+
+        /// Which part of the images to display, and how to orient them
+        #[command(flatten)]
+        pub source_transform: SourceTransform,
+
+        /// Set the type of transition
+        #[command(flatten)]
+        pub transition: Transition,
+
+        /// Set the options of transition
+        #[command(flatten)]
+        pub transition_options: TransitionOptions,
+
+        /// Set the options for easing function of transition
+        #[command(flatten)]
+        pub ease: Ease,
+
+        /// Which color to fill the padding with when loaded image does not fill the screen
+        #[arg(long ,short, value_parser = parse_rgb)]
+        pub fill_rgb: Option<(u8, u8, u8)>,
+
+        /// How to fill the padding when loaded image does not fill the screen
+        #[command(flatten)]
+        pub padding: Padding,
+    }
+
+    #[derive(
+        Copy, Clone, clap::Subcommand, serde::Serialize, serde::Deserialize, Debug, PartialEq, Eq,
+    )]
+    pub enum SlideshowCommand {
+        /// Stop switching images until resumed
+        Pause,
+        /// Continue switching images
+        Resume,
+        /// Switch to the next image now
+        Next,
+        /// Switch to the previous image now
+        Prev,
+        /// Stop the slideshow, and keep the current image
+        Stop,
+    }
+
+    #[derive(
+        Copy, Clone, clap::Subcommand, serde::Serialize, serde::Deserialize, Debug, PartialEq,
+    )]
+    pub enum TransitionCommand {
+        /// Jump to the final frame
+        Cancel,
+        /// Freeze the progress until resumed. A paused transition is replaced by the next
+        /// wallpaper, even with `--no-interrupt`
+        Pause,
+        /// Continue the progress
+        Resume,
+        /// Move to the given progress
+        Seek {
+            /// Progress before easing, from `0` to `1`
+            #[arg(value_parser = parse_progress)]
+            progress: f64,
+        },
+        /// Run the running and later transitions faster or slower, e.g. `0.1` for slow motion
+        Speed {
+            /// Multiplier of the real speed. Default: 1
+            #[arg(value_parser = parse_speed)]
+            speed: f64,
+        },
+    }
+
+    fn parse_progress(s: &str) -> Result<f64> {
+        let progress: f64 = s.parse()?;
+        if !(0.0..=1.0).contains(&progress) {
+            return Err(anyhow!("Progress must be from 0 to 1"));
+        }
+
+        Ok(progress)
+    }
+
+    fn parse_speed(s: &str) -> Result<f64> {
+        let speed: f64 = s.parse()?;
+        if !speed.is_finite() || speed <= 0.0 {
+            return Err(anyhow!("Speed must be greater than zero"));
+        }
+
+        Ok(speed)
+    }
+
+    #[derive(
+        Copy, Clone, clap::ValueEnum, serde::Serialize, serde::Deserialize, Debug, PartialEq, Eq,
+    )]
+    pub enum SlideshowOrder {
+        /// Shuffled, and shuffled again after all images are displayed
+        Random,
+        /// By path
+        Name,
+        /// By modification time, oldest first
+        Mtime,
+    }
+
+    fn parse_interval(s: &str) -> Result<Duration> {
+        let interval =
+            humantime::parse_duration(s).map_err(|e| anyhow!("Failed to parse interval: {e}"))?;
+        if interval.is_zero() {
+            return Err(anyhow!("Interval must be longer than zero"));
+        }
+
+        Ok(interval)
+    }
+
+    pub fn parse_gradient_stop(s: &str) -> Result<GradientStop> {
+        let (rgb, offset) = match s.split_once('@') {
+            Some((rgb, offset)) => (rgb, Some(offset)),
+            None => (s, None),
+        };
+
+        let rgb = parse_rgb(rgb)?;
+        let offset = offset
+            .map(|offset| {
+                offset
+                    .parse::<f64>()
+                    .map_err(|e| anyhow!("Failed to parse gradient stop offset: {e}"))
+            })
+            .transpose()?;
+
+        if let Some(offset) = offset
+            && !(0.0..=1.0).contains(&offset)
+        {
+            return Err(anyhow!("Gradient stop offset must be within [0, 1]"));
+        }
+
+        Ok(GradientStop { rgb, offset })
+    }
+
+    /// Options not given fall back to the configured defaults.
+    #[derive(
+        Copy, Clone, clap::Args, serde::Serialize, serde::Deserialize, Debug, Default, PartialEq,
+    )]
+    pub struct Padding {
+        /// How to fill the padding. Default: color
+        #[arg(long)]
+        pub fill: Option<FillKind>,
+
+        /// End color of `--fill gradient`, which starts from the fill color. Default: 000000
+        #[arg(long, value_parser = parse_rgb)]
+        pub fill_rgb_end: Option<(u8, u8, u8)>,
+
+        /// Direction of `--fill gradient` in degrees, counter-clockwise from left to right.
+        /// Default: 270.0
+        #[arg(long, allow_hyphen_values = true)]
+        pub fill_angle: Option<f64>,
+    }
+
+    impl From<Fill> for Padding {
+        fn from(fill: Fill) -> Self {
+            let (fill, fill_rgb_end, fill_angle) = match fill {
+                Fill::Color => (FillKind::Color, None, None),
+                Fill::Gradient { rgb_end, angle } => {
+                    (FillKind::Gradient, Some(rgb_end), Some(angle))
+                }
+                Fill::Blur => (FillKind::Blur, None, None),
+                Fill::Mirror => (FillKind::Mirror, None, None),
+                Fill::Extend => (FillKind::Extend, None, None),
+            };
+
+            Self {
+                fill: Some(fill),
+                fill_rgb_end,
+                fill_angle,
+            }
+        }
+    }
+
+    #[derive(
+        Copy, Clone, clap::ValueEnum, serde::Serialize, serde::Deserialize, Debug, PartialEq, Eq,
+    )]
+    pub enum FillKind {
+        /// Fill the padding with the fill color
+        Color,
+        /// Fill the padding with a linear gradient from the fill color to `--fill-rgb-end`
+        Gradient,
+        /// Fill the padding with a blurred copy of the image, which covers the whole screen
+        Blur,
+        /// Fill the padding by mirroring the image over its edges
+        Mirror,
+        /// Fill the padding by stretching the edge pixels of the image
+        Extend,
+    }
+
+    /// How to fill the padding around an image, resolved from command line options and the
+    /// configured defaults.
+    #[derive(Copy, Clone, serde::Serialize, serde::Deserialize, Debug, PartialEq, Default)]
+    pub enum Fill {
+        #[default]
+        Color,
+        Gradient {
+            rgb_end: (u8, u8, u8),
+            angle: f64,
+        },
+        Blur,
+        Mirror,
+        Extend,
+    }
+
     pub const DEFAULT_TRANSITION_KIND: TransitionKind = TransitionKind::No;
     pub const DEFAULT_TRANSITION_DURATION: f64 = 3.0;
     pub const DEFAULT_TRANSITION_FPS: f64 = 30.0;
     pub const DEFAULT_WIPE_ANGLE: f64 = 0.0;
     pub const DEFAULT_EASE_KIND: EaseKind = EaseKind::No;
+    pub const DEFAULT_GRADIENT_KIND: GradientKind = GradientKind::Linear;
+    pub const DEFAULT_GRADIENT_ANGLE: f64 = 0.0;
+    /// Gradients are rendered with a fixed number of stops.
+    pub const MAX_GRADIENT_STOPS: usize = 16;
+    pub const DEFAULT_FILL_KIND: FillKind = FillKind::Color;
+    pub const DEFAULT_FILL_RGB_END: (u8, u8, u8) = (0x00, 0x00, 0x00);
+    pub const DEFAULT_FILL_ANGLE: f64 = 270.0;
+    pub const DEFAULT_SLIDESHOW_INTERVAL: Duration = Duration::from_secs(10 * 60);
+    pub const DEFAULT_SLIDESHOW_ORDER: SlideshowOrder = SlideshowOrder::Name;
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        client::{Args, ClientSubcommand},
+        server,
+    };
+    use clap::Parser;
+
+    #[test]
+    fn test_negative_gradient_angle() {
+        let args = Args::try_parse_from([
+            "pwww",
+            "gradient",
+            "000000",
+            "ffffff",
+            "--gradient-angle",
+            "-45",
+        ])
+        .unwrap();
+        let ClientSubcommand::Gradient { gradient, .. } = args.subcommand else {
+            panic!("Expected a gradient subcommand");
+        };
+        assert_eq!(gradient.angle, Some(-45.0));
+    }
+
+    #[test]
+    fn test_negative_fill_angle() {
+        let args = Args::try_parse_from([
+            "pwww",
+            "img",
+            "-",
+            "--fill",
+            "gradient",
+            "--fill-angle",
+            "-45",
+        ])
+        .unwrap();
+        let ClientSubcommand::SwitchImage { padding, .. } = args.subcommand else {
+            panic!("Expected an image subcommand");
+        };
+        assert_eq!(padding.fill_angle, Some(-45.0));
+    }
+
+    #[test]
+    fn test_negative_margin() {
+        let args = server::Args::try_parse_from(["pwwwd", "--margin", "-10,0,0,0"]).unwrap();
+        assert_eq!(args.surface.margin, Some((-10, 0, 0, 0)));
+    }
+
+    #[test]
+    fn test_negative_tile_offset() {
+        let args = Args::try_parse_from(["pwww", "img", "-", "--tile-offset", "-10,5"]).unwrap();
+        let ClientSubcommand::SwitchImage { tile, .. } = args.subcommand else {
+            panic!("Expected an image subcommand");
+        };
+        assert_eq!(tile.offset, Some((-10.0, 5.0)));
+    }
 }
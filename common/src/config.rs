@@ -0,0 +1,398 @@
+use crate::{
+    cli::{
+        client::{
+            DEFAULT_EASE_KIND, DEFAULT_FILL_ANGLE, DEFAULT_FILL_KIND, DEFAULT_FILL_RGB_END,
+            DEFAULT_TRANSITION_DURATION, DEFAULT_TRANSITION_FPS, DEFAULT_TRANSITION_KIND,
+            DEFAULT_WIPE_ANGLE, EaseKind, Fill, FillKind, Padding, TransitionKind,
+        },
+        parse_rgb,
+        server::{DEFAULT_RESIZE, RGB, ResizeOption},
+    },
+    history::DEFAULT_HISTORY_SIZE,
+    ipc,
+    palette::{AutoFillRgb, Palette, PaletteFormat},
+    schedule::ScheduleConfig,
+};
+use anyhow::{Result, anyhow};
+use clap::ValueEnum;
+use directories::BaseDirs;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+/// Configuration of the daemon, read from `$XDG_CONFIG_HOME/pwwwd/config.toml`. Options given on
+/// the command line override it.
+#[derive(Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    /// Path of the socket the daemon listens on.
+    pub socket: Option<PathBuf>,
+    /// Displayed if the wallpaper to be restored cannot be loaded. If it cannot be loaded either,
+    /// the screen is filled with the fill color.
+    pub default_image: Option<PathBuf>,
+    /// How many wallpapers are kept in the history. Default: 50
+    pub history_size: Option<usize>,
+    /// Formats the palette of the wallpaper is exported to in the state directory, whenever the
+    /// wallpaper of all outputs changes.
+    pub export_palette: Vec<PaletteFormat>,
+    /// Wallpapers changing with the time of day, which are displayed from startup.
+    pub schedule: Option<ScheduleConfig>,
+    /// Watching the images and the directories of the slideshow for changes on disk.
+    pub watch: Watch,
+    /// Commands run on events of the daemon.
+    pub hooks: Hooks,
+    /// Defaults of all outputs.
+    pub defaults: Defaults,
+    /// Defaults of specific outputs by name, e.g. `[output.DP-1]`, overriding `defaults`.
+    pub output: HashMap<String, Defaults>,
+}
+
+/// Default options of wallpapers. Options which are not set fall back to the builtin defaults.
+#[derive(Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Defaults {
+    #[serde(deserialize_with = "value_enum")]
+    pub resize: Option<ResizeOption>,
+    #[serde(deserialize_with = "rgb")]
+    pub fill_rgb: Option<(u8, u8, u8)>,
+    /// Fill with a color of the image if `fill-rgb` isn't set.
+    #[serde(deserialize_with = "value_enum")]
+    pub auto_fill_rgb: Option<AutoFillRgb>,
+    #[serde(deserialize_with = "value_enum")]
+    pub fill: Option<FillKind>,
+    #[serde(deserialize_with = "rgb")]
+    pub fill_rgb_end: Option<(u8, u8, u8)>,
+    pub fill_angle: Option<f64>,
+    #[serde(deserialize_with = "value_enum")]
+    pub transition: Option<TransitionKind>,
+    pub transition_duration: Option<f64>,
+    pub transition_fps: Option<f64>,
+    pub wipe_angle: Option<f64>,
+    #[serde(deserialize_with = "value_enum")]
+    pub ease: Option<EaseKind>,
+    #[serde(deserialize_with = "value_enum")]
+    pub mag_filter: Option<Filter>,
+    #[serde(deserialize_with = "value_enum")]
+    pub min_filter: Option<Filter>,
+    #[serde(deserialize_with = "value_enum")]
+    pub mipmap_filter: Option<Filter>,
+}
+
+/// Which files are watched, and how changed images are displayed.
+#[derive(Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Watch {
+    /// Reload the displayed images when they are written or replaced.
+    pub images: bool,
+    /// Add new images in the directories of the running slideshow to it.
+    pub directories: bool,
+    /// How long a file must stay unchanged before it is loaded, so that half-written files are not
+    /// loaded, e.g. `500ms`. Default: 500ms
+    #[serde(deserialize_with = "duration")]
+    pub debounce: Option<Duration>,
+    /// Transition into reloaded images. Options which are not set fall back to the defaults.
+    #[serde(deserialize_with = "value_enum")]
+    pub transition: Option<TransitionKind>,
+    pub transition_duration: Option<f64>,
+}
+
+pub const DEFAULT_WATCH_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Commands run by `sh -c` on events of the daemon. The wallpaper the event is about is given in
+/// `PWWW_*` environment variables.
+#[derive(Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Hooks {
+    /// Run when the wallpaper is changed, once for all outputs or once for each requested output.
+    pub wallpaper_changed: Option<String>,
+    /// Run when the transition on an output is finished.
+    pub transition_finished: Option<String>,
+    /// Run when an output is plugged in.
+    pub output_added: Option<String>,
+    pub daemon_started: Option<String>,
+    pub daemon_stopped: Option<String>,
+    /// How long a hook may run before it is killed, e.g. `10s`. Default: 10s
+    #[serde(deserialize_with = "duration")]
+    pub timeout: Option<Duration>,
+}
+
+pub const DEFAULT_HOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Filter of the image sampler.
+#[derive(Copy, Clone, clap::ValueEnum, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub enum Filter {
+    Nearest,
+    Linear,
+}
+
+pub const DEFAULT_MAG_FILTER: Filter = Filter::Linear;
+pub const DEFAULT_MIN_FILTER: Filter = Filter::Nearest;
+pub const DEFAULT_MIPMAP_FILTER: Filter = Filter::Nearest;
+
+/// Get the path of the config file.
+pub fn default_config_path() -> Result<PathBuf> {
+    let dirs = BaseDirs::new().ok_or(anyhow!(
+        "Cannot create `BaseDirs` to get default config dir"
+    ))?;
+    Ok(dirs.config_dir().join("pwwwd").join("config.toml"))
+}
+
+impl Config {
+    /// Load the config file. A missing config file is the same as an empty one.
+    pub fn load() -> Result<Self> {
+        Self::load_from(&default_config_path()?)
+    }
+
+    pub fn load_from(path: &Path) -> Result<Self> {
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(anyhow!("Cannot read config file `{}`: {e}", path.display())),
+        };
+
+        // The error reports the line and the column of the invalid option.
+        toml::from_str(&content)
+            .map_err(|e| anyhow!("Invalid config file `{}`: {e}", path.display()))
+    }
+
+    pub fn history_size(&self) -> usize {
+        self.history_size.unwrap_or(DEFAULT_HISTORY_SIZE)
+    }
+
+    pub fn socket_path(&self) -> Result<PathBuf> {
+        match &self.socket {
+            Some(socket) => Ok(socket.clone()),
+            None => ipc::default_uds_path(),
+        }
+    }
+
+    /// Defaults of the output, falling back to the global defaults.
+    pub fn defaults_for(&self, output: Option<&str>) -> Defaults {
+        match output.and_then(|name| self.output.get(name)) {
+            Some(defaults) => defaults.or(&self.defaults),
+            None => self.defaults.clone(),
+        }
+    }
+}
+
+impl Watch {
+    pub fn debounce(&self) -> Duration {
+        self.debounce.unwrap_or(DEFAULT_WATCH_DEBOUNCE)
+    }
+}
+
+impl Hooks {
+    pub fn timeout(&self) -> Duration {
+        self.timeout.unwrap_or(DEFAULT_HOOK_TIMEOUT)
+    }
+}
+
+impl Defaults {
+    /// Options set in `self`, or in `other` if they are not.
+    pub fn or(&self, other: &Defaults) -> Defaults {
+        Defaults {
+            resize: self.resize.or(other.resize),
+            fill_rgb: self.fill_rgb.or(other.fill_rgb),
+            auto_fill_rgb: self.auto_fill_rgb.or(other.auto_fill_rgb),
+            fill: self.fill.or(other.fill),
+            fill_rgb_end: self.fill_rgb_end.or(other.fill_rgb_end),
+            fill_angle: self.fill_angle.or(other.fill_angle),
+            transition: self.transition.or(other.transition),
+            transition_duration: self.transition_duration.or(other.transition_duration),
+            transition_fps: self.transition_fps.or(other.transition_fps),
+            wipe_angle: self.wipe_angle.or(other.wipe_angle),
+            ease: self.ease.or(other.ease),
+            mag_filter: self.mag_filter.or(other.mag_filter),
+            min_filter: self.min_filter.or(other.min_filter),
+            mipmap_filter: self.mipmap_filter.or(other.mipmap_filter),
+        }
+    }
+
+    pub fn resize(&self, resize: Option<ResizeOption>) -> ResizeOption {
+        resize.or(self.resize).unwrap_or(DEFAULT_RESIZE)
+    }
+
+    /// The fill color, which may come from the palette of the image.
+    pub fn fill_rgb(
+        &self,
+        fill_rgb: Option<(u8, u8, u8)>,
+        palette: Option<&Palette>,
+    ) -> (u8, u8, u8) {
+        fill_rgb
+            .or(self.fill_rgb)
+            .or_else(|| Some(palette?.fill_rgb(self.auto_fill_rgb?)))
+            .unwrap_or(RGB)
+    }
+
+    pub fn fill(&self, padding: &Padding) -> Fill {
+        match padding.fill.or(self.fill).unwrap_or(DEFAULT_FILL_KIND) {
+            FillKind::Color => Fill::Color,
+            FillKind::Gradient => Fill::Gradient {
+                rgb_end: padding
+                    .fill_rgb_end
+                    .or(self.fill_rgb_end)
+                    .unwrap_or(DEFAULT_FILL_RGB_END),
+                angle: padding
+                    .fill_angle
+                    .or(self.fill_angle)
+                    .unwrap_or(DEFAULT_FILL_ANGLE),
+            },
+            FillKind::Blur => Fill::Blur,
+            FillKind::Mirror => Fill::Mirror,
+            FillKind::Extend => Fill::Extend,
+        }
+    }
+
+    pub fn transition(&self, transition: Option<TransitionKind>) -> TransitionKind {
+        transition
+            .or(self.transition)
+            .unwrap_or(DEFAULT_TRANSITION_KIND)
+    }
+
+    pub fn transition_duration(&self, duration: Option<f64>) -> f64 {
+        duration
+            .or(self.transition_duration)
+            .unwrap_or(DEFAULT_TRANSITION_DURATION)
+    }
+
+    pub fn transition_fps(&self, fps: Option<f64>) -> f64 {
+        fps.or(self.transition_fps)
+            .unwrap_or(DEFAULT_TRANSITION_FPS)
+    }
+
+    pub fn wipe_angle(&self, wipe_angle: Option<f64>) -> f64 {
+        wipe_angle.or(self.wipe_angle).unwrap_or(DEFAULT_WIPE_ANGLE)
+    }
+
+    pub fn ease(&self, ease: Option<EaseKind>) -> EaseKind {
+        ease.or(self.ease).unwrap_or(DEFAULT_EASE_KIND)
+    }
+
+    /// Magnification, minification and mipmap filters of the image sampler.
+    pub fn filters(&self) -> (Filter, Filter, Filter) {
+        (
+            self.mag_filter.unwrap_or(DEFAULT_MAG_FILTER),
+            self.min_filter.unwrap_or(DEFAULT_MIN_FILTER),
+            self.mipmap_filter.unwrap_or(DEFAULT_MIPMAP_FILTER),
+        )
+    }
+}
+
+/// Deserialize a value by the name used on the command line, e.g. `integer-crop`.
+pub(crate) fn value_enum<'de, D: Deserializer<'de>, T: ValueEnum>(
+    deserializer: D,
+) -> Result<Option<T>, D::Error> {
+    let s = String::deserialize(deserializer)?;
+    T::from_str(&s, false).map(Some).map_err(|_| {
+        let possible_values = T::value_variants()
+            .iter()
+            .filter_map(|value| value.to_possible_value())
+            .map(|value| value.get_name().to_string())
+            .collect::<Vec<_>>();
+        serde::de::Error::custom(format!(
+            "invalid value `{s}`, expected one of: {}",
+            possible_values.join(", ")
+        ))
+    })
+}
+
+/// Deserialize a color in the form of `RRGGBB`.
+pub(crate) fn rgb<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<(u8, u8, u8)>, D::Error> {
+    let s = String::deserialize(deserializer)?;
+    parse_rgb(&s)
+        .map(Some)
+        .map_err(|e| serde::de::Error::custom(format!("invalid color `{s}`: {e}")))
+}
+
+/// Deserialize a duration in the form of `500ms`, `1m 30s`, etc.
+fn duration<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Duration>, D::Error> {
+    let s = String::deserialize(deserializer)?;
+    humantime::parse_duration(&s)
+        .map(Some)
+        .map_err(|e| serde::de::Error::custom(format!("invalid duration `{s}`: {e}")))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Config, Filter};
+    use crate::{
+        cli::{
+            client::{EaseKind, Fill, Padding, TransitionKind},
+            server::ResizeOption,
+        },
+        palette::{Palette, PaletteFormat},
+    };
+    use std::time::Duration;
+
+    #[test]
+    fn test_per_output_defaults() {
+        let config: Config = toml::from_str(
+            r#"
+            socket = "/tmp/pwwwd.sock"
+            default-image = "/usr/share/backgrounds/default.png"
+            export-palette = ["css", "shell"]
+
+            [defaults]
+            resize = "integer-crop"
+            auto-fill-rgb = "edge"
+            fill = "gradient"
+            fill-rgb-end = "ffffff"
+            transition = "wipe"
+            ease = "ease-in-out-cubic"
+            min-filter = "linear"
+
+            [output.DP-1]
+            resize = "fit"
+            transition-duration = 1.5
+
+            [watch]
+            images = true
+            debounce = "1s 500ms"
+            "#,
+        )
+        .unwrap();
+
+        let defaults = config.defaults_for(Some("DP-1"));
+        assert_eq!(defaults.resize(None), ResizeOption::Fit);
+        assert_eq!(defaults.resize(Some(ResizeOption::No)), ResizeOption::No);
+        assert_eq!(defaults.transition(None), TransitionKind::Wipe);
+        assert_eq!(defaults.transition_duration(None), 1.5);
+        assert_eq!(defaults.ease(None), EaseKind::EaseInOutCubic);
+        assert_eq!(defaults.filters().1, Filter::Linear);
+        assert_eq!(
+            defaults.fill(&Padding::default()),
+            Fill::Gradient {
+                rgb_end: (0xff, 0xff, 0xff),
+                angle: 270.0
+            }
+        );
+
+        let defaults = config.defaults_for(Some("HDMI-A-1"));
+        assert_eq!(defaults.resize(None), ResizeOption::IntegerCrop);
+        assert_eq!(defaults.transition_duration(None), 3.0);
+
+        assert_eq!(
+            config.export_palette,
+            vec![PaletteFormat::Css, PaletteFormat::Shell]
+        );
+        let palette = Palette::from_colors(&[(0x10, 0x20, 0x30)]);
+        assert_eq!(defaults.fill_rgb(None, Some(&palette)), (0x10, 0x20, 0x30));
+        assert_eq!(defaults.fill_rgb(None, None), (0x22, 0x44, 0x66));
+
+        assert!(config.watch.images && !config.watch.directories);
+        assert_eq!(config.watch.debounce(), Duration::from_millis(1500));
+    }
+
+    #[test]
+    fn test_config_error_location() {
+        let e = toml::from_str::<Config>("[defaults]\nresize = \"fill\"\n").unwrap_err();
+        let message = e.to_string();
+        assert!(message.contains("line 2, column 10"), "{message}");
+        assert!(message.contains("expected one of"), "{message}");
+    }
+}
@@ -0,0 +1,193 @@
+use crate::ipc::{ImageArgs, ImageSource};
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    io::{Read, Write},
+};
+
+pub const DEFAULT_HISTORY_SIZE: usize = 50;
+
+/// Wallpapers applied by the client, oldest first. Going back and forth keeps the entries, and a
+/// new wallpaper drops the entries after the displayed one, like the history of a browser.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct History {
+    pub entries: VecDeque<ImageArgs>,
+    /// Index of the displayed entry. Meaningless if there is no entry.
+    pub position: usize,
+    /// The displayed wallpaper isn't recorded, so going back displays the entry at `position`
+    /// again instead of the one before it.
+    #[serde(default)]
+    pub unrecorded: bool,
+}
+
+impl History {
+    /// Record `args` as the displayed wallpaper, keeping at most `size` entries. Image data sent
+    /// through the socket isn't recorded, because it cannot be loaded again.
+    pub fn push(&mut self, args: ImageArgs, size: usize) {
+        if !self.entries.is_empty() {
+            self.entries.truncate(self.position + 1);
+        }
+        self.unrecorded = matches!(args.image, ImageSource::Data(_));
+        if self.unrecorded {
+            return;
+        }
+
+        self.entries.push_back(args);
+        while self.entries.len() > size.max(1) {
+            self.entries.pop_front();
+        }
+        self.position = self.entries.len() - 1;
+    }
+
+    /// The entry `offset` entries after the displayed one, or before it if `offset` is negative.
+    pub fn peek(&self, offset: isize) -> Option<&ImageArgs> {
+        self.entries.get(self.target(offset)?)
+    }
+
+    /// Display the entry `offset` entries after the displayed one, and return it. Nothing is
+    /// changed if there is no such entry.
+    pub fn step(&mut self, offset: isize) -> Option<&ImageArgs> {
+        self.position = self.target(offset)?;
+        self.unrecorded = false;
+        self.entries.get(self.position)
+    }
+
+    /// Index of the entry `offset` entries after the displayed one.
+    fn target(&self, offset: isize) -> Option<usize> {
+        // The entry at `position` is the one right before an unrecorded wallpaper.
+        let offset = match self.unrecorded && offset < 0 {
+            true => offset + 1,
+            false => offset,
+        };
+        self.position
+            .checked_add_signed(offset)
+            .filter(|position| *position < self.entries.len())
+    }
+
+    pub fn deserialize_from<R: Read>(reader: R) -> Result<Self> {
+        serde_json::from_reader(reader).map_err(|e| anyhow!("Cannot deserialize `History`: {e}"))
+    }
+
+    pub fn serialize_to<W: Write>(&self, writer: W) -> Result<()> {
+        serde_json::to_writer_pretty(writer, self)
+            .map_err(|e| anyhow!("Cannot serialize `History`: {e}"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::History;
+    use crate::{
+        cli::client::{Padding, Placement, SourceTransform, TileOptions, TransitionOptions},
+        ipc::{ImageArgs, ImageSource},
+    };
+    use std::path::PathBuf;
+
+    fn image_args(path: &str) -> ImageArgs {
+        ImageArgs {
+            image: ImageSource::Path(PathBuf::from(path)),
+            outputs: vec![],
+            resize: None,
+            tile: TileOptions::default(),
+            placement: Placement::default(),
+            source_transform: SourceTransform::default(),
+            fill_rgb: None,
+            padding: Padding::default(),
+            transition: None,
+            transition_options: TransitionOptions::default(),
+            ease: None,
+        }
+    }
+
+    fn image(args: Option<&ImageArgs>) -> Option<ImageSource> {
+        args.map(|args| args.image.clone())
+    }
+
+    fn source(path: &str) -> Option<ImageSource> {
+        Some(ImageSource::Path(PathBuf::from(path)))
+    }
+
+    #[test]
+    fn test_history_back_and_forth() {
+        let mut history = History::default();
+        assert!(history.peek(-1).is_none());
+
+        for path in ["/a.png", "/b.png", "/c.png"] {
+            history.push(image_args(path), 10);
+        }
+        assert!(history.peek(1).is_none());
+        assert_eq!(image(history.peek(-1)), source("/b.png"));
+
+        assert_eq!(image(history.step(-1)), source("/b.png"));
+        assert_eq!(image(history.step(-1)), source("/a.png"));
+        assert!(history.peek(-1).is_none());
+        assert!(history.step(-1).is_none());
+        assert_eq!(image(history.peek(0)), source("/a.png"));
+
+        // A new wallpaper drops the entries after the displayed one.
+        history.push(image_args("/d.png"), 10);
+        assert_eq!(history.entries.len(), 2);
+        assert!(history.peek(1).is_none());
+        assert_eq!(image(history.peek(-1)), source("/a.png"));
+    }
+
+    #[test]
+    fn test_history_unrecorded() {
+        let data = ImageArgs {
+            image: ImageSource::Data(vec![0; 16]),
+            ..image_args("/")
+        };
+
+        let mut history = History::default();
+        for path in ["/a.png", "/b.png", "/c.png"] {
+            history.push(image_args(path), 10);
+        }
+        history.step(-1);
+        // Image data drops the entries after `/b.png`, and going back displays `/b.png` again.
+        history.push(data.clone(), 10);
+        assert_eq!(history.entries.len(), 2);
+        assert!(history.peek(1).is_none());
+        assert_eq!(image(history.peek(-1)), source("/b.png"));
+        assert_eq!(image(history.peek(-2)), source("/a.png"));
+
+        history.step(-1);
+        assert_eq!(image(history.peek(0)), source("/b.png"));
+        assert_eq!(image(history.peek(-1)), source("/a.png"));
+
+        // Going back and forth with several unrecorded wallpapers in a row.
+        history.push(data.clone(), 10);
+        history.push(data, 10);
+        assert_eq!(image(history.peek(-1)), source("/b.png"));
+        history.push(image_args("/d.png"), 10);
+        assert_eq!(image(history.peek(-1)), source("/b.png"));
+        history.step(-1);
+        assert_eq!(image(history.peek(1)), source("/d.png"));
+    }
+
+    #[test]
+    fn test_history_size() {
+        let mut history = History::default();
+        for i in 0..5 {
+            history.push(image_args(&format!("/{i}.png")), 3);
+        }
+        history.push(
+            ImageArgs {
+                image: ImageSource::Data(vec![0; 16]),
+                ..image_args("/")
+            },
+            3,
+        );
+
+        assert_eq!(history.entries.len(), 3);
+        assert_eq!(history.position, 2);
+        assert_eq!(image(history.peek(-3)), source("/2.png"));
+
+        let mut buf = vec![];
+        history.serialize_to(&mut buf).unwrap();
+        let history = History::deserialize_from(&buf[..]).unwrap();
+        assert_eq!(history.entries.len(), 3);
+        assert_eq!(history.position, 2);
+        assert!(history.unrecorded);
+    }
+}
@@ -1,10 +1,12 @@
 use crate::cli::{
+    STDIN_PATH, STDOUT_PATH,
     client::{
-        ClientSubcommand, DEFAULT_EASE_KIND, DEFAULT_TRANSITION_KIND, EaseKind, ResizeOption,
+        self, ClientSubcommand, EaseKind, Fill, Gradient, Padding, Placement, ResizeOption,
+        SlideshowCommand, SlideshowOrder, SourceTransform, TileOptions, TransitionCommand,
         TransitionKind, TransitionOptions,
     },
-    server::{DEFAULT_RESIZE, RGB},
 };
+use crate::{history::History, palette::Palette};
 use anyhow::{Result, anyhow};
 use rmp_serde::{Deserializer, Serializer};
 use serde::{Deserialize, Serialize};
@@ -12,79 +14,310 @@ use std::{
     fmt::Display,
     io::{Read, Write},
     path::PathBuf,
+    time::Duration,
 };
 
 /// The daemon's reply type. Following a 4-byte `length` big-endian message in socket stream.
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[allow(clippy::large_enum_variant)]
 pub enum Message {
     Kill,
     Image {
         #[serde(flatten)]
         args: ImageArgs,
     },
+    /// List the outputs known by the daemon.
+    Outputs,
+    /// Re-read the config file, and apply it in place.
+    Reload,
+    /// Start a slideshow, replacing the running one.
+    Slideshow {
+        args: SlideshowArgs,
+    },
+    /// Control the running slideshow.
+    SlideshowControl {
+        command: SlideshowCommand,
+    },
+    /// Control the running transitions.
+    Transition {
+        command: TransitionCommand,
+    },
+    /// Go back to the previous wallpaper in the history.
+    Prev,
+    /// Go forward to the next wallpaper in the history.
+    Next,
+    History,
+    /// The palette of the wallpaper of an output, or of all outputs if `None`.
+    Colors {
+        output: Option<String>,
+    },
+    /// Capture the frames displayed on an output, or on all outputs if `None`.
+    Screenshot {
+        output: Option<String>,
+        /// The PNG file to write, or the directory to write one PNG per output to if `output` is
+        /// `None`. If `None`, the PNGs are sent in the reply instead.
+        path: Option<PathBuf>,
+    },
 }
 
+/// Options which are `None` are not given by the user, and fall back to the defaults configured
+/// for each output by the daemon.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ImageArgs {
-    pub path: PathBuf,
-    pub resize: ResizeOption,
-    pub fill_rgb: (u8, u8, u8),
-    pub transition: TransitionKind,
+    pub image: ImageSource,
+    /// Names of the outputs to change the wallpaper of. Empty for all outputs.
+    pub outputs: Vec<String>,
+    pub resize: Option<ResizeOption>,
+    pub tile: TileOptions,
+    pub placement: Placement,
+    pub source_transform: SourceTransform,
+    pub fill_rgb: Option<(u8, u8, u8)>,
+    pub padding: Padding,
+    pub transition: Option<TransitionKind>,
+    pub transition_options: TransitionOptions,
+    pub ease: Option<EaseKind>,
+}
+
+/// Options which are `None` fall back to the defaults, like `ImageArgs`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SlideshowArgs {
+    /// Directories to pick images from, or images.
+    pub paths: Vec<PathBuf>,
+    pub interval: Option<Duration>,
+    pub order: Option<SlideshowOrder>,
+    pub recursive: bool,
+    pub resize: Option<ResizeOption>,
+    pub tile: TileOptions,
+    pub placement: Placement,
+    pub source_transform: SourceTransform,
+    pub fill_rgb: Option<(u8, u8, u8)>,
+    pub padding: Padding,
+    pub transition: Option<TransitionKind>,
     pub transition_options: TransitionOptions,
-    pub ease: EaseKind,
+    pub ease: Option<EaseKind>,
+}
+
+impl SlideshowArgs {
+    /// Display `image` of the slideshow on all outputs.
+    pub fn image_args(&self, image: PathBuf) -> ImageArgs {
+        ImageArgs {
+            image: ImageSource::Path(image),
+            outputs: vec![],
+            resize: self.resize,
+            tile: self.tile,
+            placement: self.placement,
+            source_transform: self.source_transform,
+            fill_rgb: self.fill_rgb,
+            padding: self.padding,
+            transition: self.transition,
+            transition_options: self.transition_options,
+            ease: self.ease,
+        }
+    }
+}
+
+impl From<client::SlideshowArgs> for SlideshowArgs {
+    fn from(args: client::SlideshowArgs) -> Self {
+        Self {
+            paths: args.paths,
+            interval: args.interval,
+            order: args.order,
+            recursive: args.recursive,
+            resize: args.resize.into_option(),
+            tile: args.tile,
+            placement: args.placement,
+            source_transform: args.source_transform,
+            fill_rgb: args.fill_rgb,
+            padding: args.padding,
+            transition: args.transition.into_option(),
+            transition_options: args.transition_options,
+            ease: args.ease.into_option(),
+        }
+    }
+}
+
+/// Where the daemon gets the new image from.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub enum ImageSource {
+    /// A path which can be read by the daemon.
+    Path(PathBuf),
+    /// Encoded image data sent through the socket, e.g. the content of a PNG file.
+    Data(#[serde(with = "serde_bytes")] Vec<u8>),
+    /// A solid color, which doesn't need any image file.
+    Color((u8, u8, u8)),
+    /// A gradient rendered by the daemon, which doesn't need any image file.
+    Gradient(Gradient),
+}
+
+impl ImageSource {
+    /// Read the whole stdin as encoded image data.
+    pub fn from_stdin() -> Result<Self> {
+        let mut buf = vec![];
+        std::io::stdin()
+            .read_to_end(&mut buf)
+            .map_err(|e| anyhow!("Failed to read image data from stdin: {e}"))?;
+
+        if buf.is_empty() {
+            return Err(anyhow!("No image data is read from stdin"));
+        }
+
+        Ok(Self::Data(buf))
+    }
+}
+
+impl ImageSource {
+    /// A short description for listings, without dumping the whole image.
+    pub fn summary(&self) -> String {
+        match self {
+            Self::Path(path) => path.display().to_string(),
+            Self::Data(data) => format!("<{} bytes of image data>", data.len()),
+            Self::Color((r, g, b)) => format!("#{r:02x}{g:02x}{b:02x}"),
+            Self::Gradient(gradient) => format!("<{:?} gradient>", gradient.kind),
+        }
+    }
+}
+
+impl std::fmt::Debug for ImageSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Path(path) => f.debug_tuple("Path").field(path).finish(),
+            // Do not dump the whole image into logs.
+            Self::Data(data) => write!(f, "Data({} bytes)", data.len()),
+            Self::Color(rgb) => f.debug_tuple("Color").field(rgb).finish(),
+            Self::Gradient(gradient) => f.debug_tuple("Gradient").field(gradient).finish(),
+        }
+    }
 }
 
 impl Message {
-    pub fn from_cli_command(cli: ClientSubcommand) -> Self {
+    pub fn from_cli_command(cli: ClientSubcommand) -> Result<Self> {
         match cli {
             ClientSubcommand::SwitchImage {
                 image,
+                outputs,
                 resize,
+                tile,
+                placement,
+                source_transform,
                 transition,
                 transition_options,
                 ease,
                 fill_rgb,
+                padding,
             } => {
-                let resize = if resize.no_resize {
-                    ResizeOption::No
-                } else {
-                    resize.resize.unwrap_or(DEFAULT_RESIZE)
-                };
-
-                let transition = if transition.no_transition {
-                    TransitionKind::No
+                let image = if image.as_os_str() == STDIN_PATH {
+                    ImageSource::from_stdin()?
                 } else {
-                    transition.transition.unwrap_or(DEFAULT_TRANSITION_KIND)
+                    ImageSource::Path(image)
                 };
 
-                let ease = if ease.no_ease {
-                    EaseKind::No
-                } else if let Some((px1, py1, px2, py2)) = ease.cubic_curve {
-                    EaseKind::CubicBezier(px1, py1, px2, py2)
-                } else {
-                    ease.ease.unwrap_or(DEFAULT_EASE_KIND)
-                };
-
-                let fill_rgb = fill_rgb.unwrap_or(RGB);
-
-                Self::Image {
+                Ok(Self::Image {
                     args: ImageArgs {
-                        path: image,
-                        resize,
-                        transition,
+                        image,
+                        outputs,
+                        resize: resize.into_option(),
+                        tile,
+                        placement,
+                        source_transform,
+                        transition: transition.into_option(),
                         transition_options,
-                        ease,
+                        ease: ease.into_option(),
                         fill_rgb,
+                        padding,
                     },
+                })
+            }
+            ClientSubcommand::Color {
+                rgb,
+                outputs,
+                transition,
+                transition_options,
+                ease,
+            } => Ok(Self::Image {
+                args: ImageArgs {
+                    image: ImageSource::Color(rgb),
+                    outputs,
+                    // Procedural wallpapers always cover the whole screen.
+                    resize: Some(ResizeOption::Stretch),
+                    tile: TileOptions::default(),
+                    placement: Placement::default(),
+                    source_transform: SourceTransform::default(),
+                    transition: transition.into_option(),
+                    transition_options,
+                    ease: ease.into_option(),
+                    fill_rgb: Some(rgb),
+                    padding: Padding::from(Fill::Color),
+                },
+            }),
+            ClientSubcommand::Gradient {
+                gradient,
+                outputs,
+                transition,
+                transition_options,
+                ease,
+            } => Ok(Self::Image {
+                args: ImageArgs {
+                    image: ImageSource::Gradient(gradient.into_gradient()?),
+                    outputs,
+                    resize: Some(ResizeOption::Stretch),
+                    tile: TileOptions::default(),
+                    placement: Placement::default(),
+                    source_transform: SourceTransform::default(),
+                    transition: transition.into_option(),
+                    transition_options,
+                    ease: ease.into_option(),
+                    fill_rgb: None,
+                    padding: Padding::from(Fill::Color),
+                },
+            }),
+            ClientSubcommand::Outputs { json: _ } => Ok(Self::Outputs),
+            ClientSubcommand::Slideshow {
+                command: Some(command),
+                args: _,
+            } => Ok(Self::SlideshowControl { command }),
+            ClientSubcommand::Slideshow {
+                command: None,
+                args,
+            } => Ok(Self::Slideshow { args: args.into() }),
+            ClientSubcommand::Transition { command } => Ok(Self::Transition { command }),
+            ClientSubcommand::Prev => Ok(Self::Prev),
+            ClientSubcommand::Next => Ok(Self::Next),
+            ClientSubcommand::History { json: _ } => Ok(Self::History),
+            ClientSubcommand::Colors { output, json: _ } => Ok(Self::Colors { output }),
+            ClientSubcommand::Screenshot { output, path } => {
+                let path = (path.as_os_str() != STDOUT_PATH).then_some(path);
+                if output.is_none() && path.is_none() {
+                    return Err(anyhow!(
+                        "Only one output can be written to stdout. Give `--output`, or a \
+                        directory to save the screenshots of all outputs to"
+                    ));
                 }
+
+                Ok(Self::Screenshot { output, path })
             }
-            ClientSubcommand::Kill => Self::Kill,
+            ClientSubcommand::Reload => Ok(Self::Reload),
+            ClientSubcommand::Kill => Ok(Self::Kill),
             ClientSubcommand::Completion { shell: _ } => {
                 panic!("`Completion` is not a valid message to be sent")
             }
         }
     }
 
+    /// Whether the message only reads the state of the daemon, which can be handled along with
+    /// other requests.
+    pub fn is_query(&self) -> bool {
+        matches!(
+            self,
+            Self::Outputs | Self::History | Self::Colors { .. } | Self::Screenshot { .. }
+        )
+    }
+
+    /// Whether the message controls the running transitions, which must be handled even while
+    /// they are running.
+    pub fn controls_transition(&self) -> bool {
+        matches!(self, Self::Transition { .. })
+    }
+
     pub fn send<T: Write>(&self, socket: &mut T) -> Result<()> {
         let mut buf = vec![];
         self.serialize(&mut Serializer::new(&mut buf))?;
@@ -155,6 +388,54 @@ pub fn default_uds_path() -> Result<PathBuf> {
 pub enum Reply {
     Ok,
     Error(String),
+    Outputs(Vec<OutputInfo>),
+    /// The config file is reloaded, except the options listed, which cannot change without
+    /// restarting the daemon.
+    Reloaded(Vec<String>),
+    History(History),
+    Colors(Palette),
+    Screenshots(Vec<Screenshot>),
+}
+
+/// The frame displayed on an output.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Screenshot {
+    pub output: Option<String>,
+    /// Size in physical pixels.
+    pub size: (u32, u32),
+    /// Where the PNG is written to, or `None` if it is sent in `png`.
+    pub path: Option<PathBuf>,
+    #[serde(with = "serde_bytes")]
+    pub png: Option<Vec<u8>>,
+}
+
+/// An output as reported by the compositor, and the wallpaper shown on it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OutputInfo {
+    /// Connector name, e.g. `DP-1`.
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub make: String,
+    pub model: String,
+    pub logical_position: Option<(i32, i32)>,
+    pub logical_size: Option<(i32, i32)>,
+    /// The current mode of the output in physical pixels.
+    pub mode: Option<OutputMode>,
+    /// The scale the wallpaper is rendered at, which can be fractional.
+    pub scale: f64,
+    /// The transform of the output, e.g. `90` or `flipped-270`.
+    pub transform: String,
+    /// `None` if the output has no wallpaper surface.
+    pub wallpaper: Option<String>,
+    pub resize: Option<ResizeOption>,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct OutputMode {
+    pub width: i32,
+    pub height: i32,
+    /// Refresh rate in mHz.
+    pub refresh: i32,
 }
 
 impl Reply {
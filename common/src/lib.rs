@@ -1,4 +1,8 @@
 pub mod cli;
+pub mod config;
+pub mod history;
 pub mod ipc;
+pub mod palette;
 pub mod restore;
+pub mod schedule;
 pub mod utils;
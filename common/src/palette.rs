@@ -0,0 +1,295 @@
+use serde::{Deserialize, Serialize};
+
+/// How many dominant colors are extracted.
+pub const PALETTE_SIZE: usize = 8;
+
+/// At most this many pixels are sampled, so that large images don't take long.
+const MAX_SAMPLES: usize = 1 << 16;
+
+/// Text of this contrast ratio against the background is readable, as in WCAG AA.
+const MIN_CONTRAST: f64 = 4.5;
+
+/// Colors extracted from a wallpaper.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Palette {
+    /// Dominant colors, the most common first.
+    pub colors: Vec<PaletteColor>,
+    pub average: (u8, u8, u8),
+    /// Average color of the pixels on the edges, which blends into the padding.
+    pub edge: (u8, u8, u8),
+    /// Average relative luminance within `[0, 1]`.
+    pub luminance: f64,
+    /// Suggested background color, e.g. for bars and terminals.
+    pub background: (u8, u8, u8),
+    /// Suggested foreground color, which is readable on `background`.
+    pub foreground: (u8, u8, u8),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct PaletteColor {
+    pub rgb: (u8, u8, u8),
+    /// How much of the image is close to this color, within `[0, 1]`.
+    pub proportion: f64,
+}
+
+/// Which color of the palette fills the padding when the fill color isn't set.
+#[derive(Copy, Clone, clap::ValueEnum, Debug, PartialEq, Eq)]
+pub enum AutoFillRgb {
+    /// The average color of the edges of the image
+    Edge,
+    /// The average color of the whole image
+    Average,
+}
+
+/// Formats the palette can be exported to.
+#[derive(Copy, Clone, clap::ValueEnum, Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum PaletteFormat {
+    Json,
+    /// Custom properties of `:root`, e.g. `--pwww-color0`.
+    Css,
+    /// Variable assignments which can be sourced by shells, e.g. `color0='#224466'`.
+    Shell,
+}
+
+impl Palette {
+    /// Extract the palette of an image of RGBA pixels, row by row.
+    pub fn from_rgba(rgba: &[u8], width: u32, height: u32) -> Self {
+        let pixel = |x: u32, y: u32| {
+            let i = (y as usize * width as usize + x as usize) * 4;
+            (rgba[i], rgba[i + 1], rgba[i + 2])
+        };
+
+        let count = width as usize * height as usize;
+        let step = count.div_ceil(MAX_SAMPLES).max(1);
+        let samples = (0..count)
+            .step_by(step)
+            .map(|i| pixel((i % width as usize) as u32, (i / width as usize) as u32))
+            .collect::<Vec<_>>();
+
+        let edges = (0..width)
+            .flat_map(|x| [pixel(x, 0), pixel(x, height.saturating_sub(1))])
+            .chain((0..height).flat_map(|y| [pixel(0, y), pixel(width.saturating_sub(1), y)]))
+            .collect::<Vec<_>>();
+
+        Self::new(samples, average_color(&edges))
+    }
+
+    /// The palette of a wallpaper drawn with `colors` only, e.g. a gradient.
+    pub fn from_colors(colors: &[(u8, u8, u8)]) -> Self {
+        Self::new(colors.to_vec(), average_color(colors))
+    }
+
+    fn new(pixels: Vec<(u8, u8, u8)>, edge: (u8, u8, u8)) -> Self {
+        let total = pixels.len().max(1) as f64;
+        let luminance = pixels.iter().map(|rgb| luminance(*rgb)).sum::<f64>() / total;
+        let average = average_color(&pixels);
+
+        // Buckets of the same color are split from a large area of that color.
+        let mut colors: Vec<PaletteColor> = vec![];
+        for bucket in median_cut(pixels, PALETTE_SIZE) {
+            let rgb = average_color(&bucket);
+            let proportion = bucket.len() as f64 / total;
+            match colors.iter_mut().find(|color| color.rgb == rgb) {
+                Some(color) => color.proportion += proportion,
+                None => colors.push(PaletteColor { rgb, proportion }),
+            }
+        }
+        colors.sort_by(|a, b| b.proportion.total_cmp(&a.proportion));
+
+        // The most common color is the background. The foreground is the most common color which
+        // is readable on it, or black or white if none is.
+        let background = colors.first().map_or(average, |color| color.rgb);
+        let foreground = colors
+            .iter()
+            .map(|color| color.rgb)
+            .chain([(0, 0, 0), (0xff, 0xff, 0xff)])
+            .find(|rgb| contrast(*rgb, background) >= MIN_CONTRAST)
+            .unwrap_or_else(|| {
+                if contrast((0, 0, 0), background) > contrast((0xff, 0xff, 0xff), background) {
+                    (0, 0, 0)
+                } else {
+                    (0xff, 0xff, 0xff)
+                }
+            });
+
+        Self {
+            colors,
+            average,
+            edge,
+            luminance,
+            background,
+            foreground,
+        }
+    }
+
+    pub fn fill_rgb(&self, auto_fill_rgb: AutoFillRgb) -> (u8, u8, u8) {
+        match auto_fill_rgb {
+            AutoFillRgb::Edge => self.edge,
+            AutoFillRgb::Average => self.average,
+        }
+    }
+
+    pub fn export(&self, format: PaletteFormat) -> String {
+        match format {
+            PaletteFormat::Json => serde_json::to_string_pretty(self).unwrap_or_default(),
+            PaletteFormat::Css => {
+                let mut css = String::from(":root {\n");
+                for (name, rgb) in self.named_colors() {
+                    css.push_str(&format!("  --pwww-{name}: {};\n", hex(rgb)));
+                }
+                css.push_str(&format!("  --pwww-luminance: {:.3};\n}}\n", self.luminance));
+                css
+            }
+            PaletteFormat::Shell => {
+                let mut shell = String::new();
+                for (name, rgb) in self.named_colors() {
+                    shell.push_str(&format!("{name}='{}'\n", hex(rgb)));
+                }
+                shell.push_str(&format!("luminance='{:.3}'\n", self.luminance));
+                shell
+            }
+        }
+    }
+
+    /// Every color with the name it's exported by.
+    fn named_colors(&self) -> Vec<(String, (u8, u8, u8))> {
+        let mut named = self
+            .colors
+            .iter()
+            .enumerate()
+            .map(|(i, color)| (format!("color{i}"), color.rgb))
+            .collect::<Vec<_>>();
+        named.extend([
+            ("average".to_string(), self.average),
+            ("edge".to_string(), self.edge),
+            ("background".to_string(), self.background),
+            ("foreground".to_string(), self.foreground),
+        ]);
+        named
+    }
+}
+
+impl PaletteFormat {
+    /// Name of the exported file in the state directory.
+    pub fn file_name(self) -> &'static str {
+        match self {
+            Self::Json => "palette.json",
+            Self::Css => "palette.css",
+            Self::Shell => "palette.sh",
+        }
+    }
+}
+
+/// `#RRGGBB`.
+pub fn hex((r, g, b): (u8, u8, u8)) -> String {
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+/// Split the pixels into at most `count` buckets of similar colors, by halving the bucket with the
+/// widest channel at its median again and again.
+fn median_cut(pixels: Vec<(u8, u8, u8)>, count: usize) -> Vec<Vec<(u8, u8, u8)>> {
+    let channel = |rgb: &(u8, u8, u8), i: usize| [rgb.0, rgb.1, rgb.2][i];
+    let widest = |bucket: &[(u8, u8, u8)]| {
+        (0..3)
+            .map(|i| {
+                let min = bucket.iter().map(|rgb| channel(rgb, i)).min().unwrap_or(0);
+                let max = bucket.iter().map(|rgb| channel(rgb, i)).max().unwrap_or(0);
+                (max - min, i)
+            })
+            .max()
+            .unwrap_or((0, 0))
+    };
+
+    let mut buckets = vec![pixels];
+    while buckets.len() < count {
+        let Some((i, (_, c))) = buckets
+            .iter()
+            .map(|bucket| widest(bucket))
+            .enumerate()
+            .filter(|(_, (range, _))| *range > 0)
+            .max_by_key(|(i, (range, _))| (*range as usize) * buckets[*i].len())
+        else {
+            break;
+        };
+
+        let mut bucket = buckets.swap_remove(i);
+        bucket.sort_unstable_by_key(|rgb| channel(rgb, c));
+        let other = bucket.split_off(bucket.len() / 2);
+        buckets.push(bucket);
+        buckets.push(other);
+    }
+
+    buckets.retain(|bucket| !bucket.is_empty());
+    buckets
+}
+
+fn average_color(pixels: &[(u8, u8, u8)]) -> (u8, u8, u8) {
+    let n = pixels.len().max(1) as u64;
+    let (r, g, b) = pixels.iter().fold((0, 0, 0), |(r, g, b), rgb| {
+        (r + rgb.0 as u64, g + rgb.1 as u64, b + rgb.2 as u64)
+    });
+    ((r / n) as u8, (g / n) as u8, (b / n) as u8)
+}
+
+/// Relative luminance of an sRGB color, as defined by WCAG.
+fn luminance((r, g, b): (u8, u8, u8)) -> f64 {
+    let linear = |c: u8| {
+        let c = c as f64 / 255.0;
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    0.2126 * linear(r) + 0.7152 * linear(g) + 0.0722 * linear(b)
+}
+
+/// Contrast ratio within `[1, 21]`, as defined by WCAG.
+fn contrast(a: (u8, u8, u8), b: (u8, u8, u8)) -> f64 {
+    let (a, b) = (luminance(a), luminance(b));
+    (a.max(b) + 0.05) / (a.min(b) + 0.05)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Palette, PaletteFormat};
+
+    #[test]
+    fn test_palette() {
+        // The left three quarters are dark blue, and the right quarter is orange.
+        let (width, height) = (64, 16);
+        let rgba = (0..width * height)
+            .flat_map(|i| match i % width < 48 {
+                true => [0x10, 0x20, 0x60, 0xff],
+                false => [0xff, 0x88, 0x00, 0xff],
+            })
+            .collect::<Vec<u8>>();
+        let palette = Palette::from_rgba(&rgba, width, height);
+
+        assert_eq!(palette.colors.len(), 2);
+        assert_eq!(palette.colors[0].rgb, (0x10, 0x20, 0x60));
+        assert_eq!(palette.colors[0].proportion, 0.75);
+        assert_eq!(palette.colors[1].rgb, (0xff, 0x88, 0x00));
+        assert_eq!(palette.background, (0x10, 0x20, 0x60));
+        assert_eq!(palette.foreground, (0xff, 0x88, 0x00));
+        assert!(palette.luminance < 0.5);
+
+        let css = palette.export(PaletteFormat::Css);
+        assert!(css.contains("--pwww-color0: #102060;"));
+        let shell = palette.export(PaletteFormat::Shell);
+        assert!(shell.contains("foreground='#ff8800'"));
+        let json = palette.export(PaletteFormat::Json);
+        assert_eq!(serde_json::from_str::<Palette>(&json).unwrap(), palette);
+    }
+
+    #[test]
+    fn test_palette_of_colors() {
+        let palette = Palette::from_colors(&[(0xf0, 0xf0, 0xf0)]);
+        assert_eq!(palette.colors.len(), 1);
+        assert_eq!(palette.edge, (0xf0, 0xf0, 0xf0));
+        // Nothing is readable on a light background but black.
+        assert_eq!(palette.foreground, (0, 0, 0));
+        assert!(palette.luminance > 0.8);
+    }
+}
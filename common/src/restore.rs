@@ -1,41 +1,176 @@
-use crate::cli::server::ResizeOption;
+use crate::{
+    cli::{
+        client::{EaseKind, Fill, TransitionKind, TransitionOptions},
+        server::{Placement, ResizeOption, SourceTransform, TileOptions},
+    },
+    config::Filter,
+    ipc::{ImageSource, SlideshowArgs},
+};
 use anyhow::{Result, anyhow};
-use rmp_serde::{Deserializer, Serializer};
+use rmp_serde::Deserializer;
 use serde::{Deserialize, Serialize};
 use std::{
+    collections::BTreeMap,
     io::{Read, Write},
-    path::{Path, PathBuf},
+    path::PathBuf,
 };
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// Version of the restore file written by this build. Bump it when a field changes in a way that
+/// `#[serde(default)]` cannot handle, and migrate the older versions in
+/// `RestoreState::deserialize_from_slice`.
+pub const RESTORE_VERSION: u32 = 1;
+
+/// Everything the daemon needs to bring the wallpapers back after a restart. Stored as JSON, so
+/// that it can be read and edited by hand.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RestoreState {
+    pub version: u32,
+    /// Displayed on outputs which don't have a wallpaper of their own.
+    pub wallpaper: Restore,
+    /// Wallpapers set for specific outputs, by output name.
+    #[serde(default)]
+    pub outputs: BTreeMap<String, Restore>,
+    /// Where the slideshow was, if one was running.
+    #[serde(default)]
+    pub slideshow: Option<SlideshowPosition>,
+}
+
+/// A wallpaper, with the options it was displayed with.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Restore {
-    pub file_path: PathBuf,
+    pub source: ImageSource,
     pub resize_option: ResizeOption,
     pub fill_rgb: (u8, u8, u8),
+    /// Missing in restore files written before fill modes were supported.
+    #[serde(default)]
+    pub fill: Fill,
+    /// Missing in restore files written before `Tile` resize option was supported.
+    #[serde(default)]
+    pub tile: TileOptions,
+    /// Missing in restore files written before alignment, zoom and pan were supported.
+    #[serde(default)]
+    pub placement: Placement,
+    /// Missing in restore files written before cropping, rotation and flipping were supported.
+    #[serde(default)]
+    pub source_transform: SourceTransform,
+    /// Magnification, minification and mipmap filters of the image sampler. Missing in msgpack
+    /// restore files.
+    #[serde(default)]
+    pub filters: Option<(Filter, Filter, Filter)>,
+    /// The transition to the wallpaper. Options which are `None` fall back to the configured
+    /// defaults.
+    #[serde(default)]
+    pub transition: Option<TransitionKind>,
+    #[serde(default)]
+    pub transition_options: TransitionOptions,
+    #[serde(default)]
+    pub ease: Option<EaseKind>,
+}
+
+/// Position of a slideshow, which is resumed from the same image.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SlideshowPosition {
+    pub args: SlideshowArgs,
+    /// The image shown when the state was saved.
+    pub current: PathBuf,
+    #[serde(default)]
+    pub paused: bool,
+}
+
+/// Restore file written before solid colors and gradients were supported, which can only store an
+/// image path.
+#[derive(Deserialize)]
+struct LegacyRestore {
+    file_path: PathBuf,
+    resize_option: ResizeOption,
+    fill_rgb: (u8, u8, u8),
+}
+
+impl From<LegacyRestore> for Restore {
+    fn from(legacy: LegacyRestore) -> Self {
+        Restore::new(
+            ImageSource::Path(legacy.file_path),
+            legacy.resize_option,
+            legacy.fill_rgb,
+        )
+    }
 }
 
 impl Restore {
-    pub fn new(
-        path: impl AsRef<Path>,
-        resize_option: ResizeOption,
-        fill_rgb: (u8, u8, u8),
-    ) -> Self {
+    /// A wallpaper with the default fill, placement and transition.
+    pub fn new(source: ImageSource, resize_option: ResizeOption, fill_rgb: (u8, u8, u8)) -> Self {
         Restore {
-            file_path: path.as_ref().to_owned(),
+            source,
             resize_option,
             fill_rgb,
+            fill: Fill::Color,
+            tile: TileOptions::default(),
+            placement: Placement::default(),
+            source_transform: SourceTransform::default(),
+            filters: None,
+            transition: None,
+            transition_options: TransitionOptions::default(),
+            ease: None,
+        }
+    }
+
+    /// Read a msgpack restore file, written before the restore file was versioned.
+    fn deserialize_from_msgpack(buf: &[u8]) -> Result<Self> {
+        match Restore::deserialize(&mut Deserializer::from_read_ref(buf)) {
+            Ok(res) => Ok(res),
+            Err(e) => LegacyRestore::deserialize(&mut Deserializer::from_read_ref(buf))
+                .map(Restore::from)
+                .map_err(|_| anyhow!("Cannot deserialize `Restore`: {e}")),
+        }
+    }
+}
+
+impl RestoreState {
+    pub fn new(wallpaper: Restore) -> Self {
+        RestoreState {
+            version: RESTORE_VERSION,
+            wallpaper,
+            outputs: BTreeMap::new(),
+            slideshow: None,
         }
     }
 
-    pub fn deserialize_from<R: Read>(reader: R) -> Result<Self> {
-        let res = Restore::deserialize(&mut Deserializer::new(reader))
-            .map_err(|e| anyhow!("Cannot deserialize `Restore`: {e}"))?;
-        Ok(res)
+    /// Read a restore file of any version. Msgpack files written by older versions are migrated.
+    fn deserialize_from_slice(buf: &[u8]) -> Result<Self> {
+        // A msgpack restore file is an array, which never starts with `{`.
+        if buf.trim_ascii_start().first() != Some(&b'{') {
+            return Restore::deserialize_from_msgpack(buf).map(RestoreState::new);
+        }
+
+        // Check the version first, because the fields of newer versions may not be understood.
+        #[derive(Deserialize)]
+        struct Version {
+            version: u32,
+        }
+        let Version { version } = serde_json::from_slice(buf)
+            .map_err(|e| anyhow!("Cannot read the version of the restore file: {e}"))?;
+        if version > RESTORE_VERSION {
+            return Err(anyhow!(
+                "The restore file is written by a newer version of pwwwd (version {version}, \
+                expected at most {RESTORE_VERSION})"
+            ));
+        }
+
+        serde_json::from_slice(buf).map_err(|e| anyhow!("Cannot deserialize `RestoreState`: {e}"))
+    }
+
+    pub fn deserialize_from<R: Read>(mut reader: R) -> Result<Self> {
+        let mut buf = vec![];
+        reader
+            .read_to_end(&mut buf)
+            .map_err(|e| anyhow!("Cannot read data to be deserialize: {e}"))?;
+
+        Self::deserialize_from_slice(&buf)
     }
 
     pub fn serialize_to<W: Write>(&self, writer: W) -> Result<()> {
-        self.serialize(&mut Serializer::new(writer))
-            .map_err(|e| anyhow!("Cannot serialize `Restore`: {e}"))?;
+        serde_json::to_writer_pretty(writer, self)
+            .map_err(|e| anyhow!("Cannot serialize `RestoreState`: {e}"))?;
         Ok(())
     }
 
@@ -49,20 +184,16 @@ impl Restore {
             .await
             .map_err(|e| anyhow!("Cannot async-read data to be deserialize: {e}"))?;
 
-        let res = Restore::deserialize(&mut Deserializer::from_read_ref(&buf))
-            .map_err(|e| anyhow!("Cannot deserialize `Restore`: {e}"))?;
-
-        Ok(res)
+        Self::deserialize_from_slice(&buf)
     }
 
     #[cfg(feature = "async")]
     pub async fn async_serialize_to<W: tokio::io::AsyncWriteExt + Unpin>(
-        &mut self,
+        &self,
         mut writer: W,
     ) -> Result<()> {
-        let mut buf = vec![];
-        self.serialize(&mut Serializer::new(&mut buf))
-            .map_err(|e| anyhow!("Cannot serialize `Restore`: {e}"))?;
+        let buf = serde_json::to_vec_pretty(self)
+            .map_err(|e| anyhow!("Cannot serialize `RestoreState`: {e}"))?;
 
         writer
             .write_all(&buf)
@@ -75,22 +206,200 @@ impl Restore {
 
 #[cfg(test)]
 mod test {
-    use std::path::Path;
+    use super::{Restore, RestoreState, SlideshowPosition};
+    use crate::{
+        cli::{
+            client::{
+                EaseKind, Fill, Gradient, GradientKind, GradientStop, Padding, SlideshowOrder,
+                TransitionKind, TransitionOptions,
+            },
+            server::{
+                Align, Flip, Placement, ResizeOption, Rotation, SourceTransform, TileOptions,
+            },
+        },
+        config::Filter,
+        ipc::{ImageSource, SlideshowArgs},
+    };
+    use serde::Serialize;
+    use std::{path::PathBuf, time::Duration};
 
     #[test]
     fn test_serde_restore() {
-        let restore = super::Restore::new(
-            Path::new("/home/test/test.jpg"),
-            crate::cli::server::ResizeOption::No,
-            (0, 0, 0),
+        let mut state = RestoreState::new(Restore {
+            fill: Fill::Gradient {
+                rgb_end: (0xff, 0xff, 0xff),
+                angle: 90.0,
+            },
+            placement: Placement {
+                align: Some(Align::TopLeft),
+                focal_point: None,
+                zoom: Some(1.5),
+                pan: Some((10.0, -20.0)),
+            },
+            source_transform: SourceTransform {
+                crop: Some((10, 20, 300, 400)),
+                rotate: Some(Rotation::Rotate90),
+                flip: Some(Flip::Horizontal),
+            },
+            filters: Some((Filter::Linear, Filter::Linear, Filter::Nearest)),
+            transition: Some(TransitionKind::Wipe),
+            transition_options: TransitionOptions {
+                duration: Some(1.5),
+                fps: None,
+                no_interrupt: false,
+                wipe_angle: Some(45.0),
+            },
+            ease: Some(EaseKind::EaseInOutCubic),
+            ..Restore::new(
+                ImageSource::Path(PathBuf::from("/home/test/test.jpg")),
+                ResizeOption::No,
+                (0, 0, 0),
+            )
+        });
+        state.outputs.insert(
+            "DP-1".to_string(),
+            Restore::new(
+                ImageSource::Color((1, 2, 3)),
+                ResizeOption::Stretch,
+                (1, 2, 3),
+            ),
         );
+        state.slideshow = Some(SlideshowPosition {
+            args: SlideshowArgs {
+                paths: vec![PathBuf::from("/home/test/wallpapers")],
+                interval: Some(Duration::from_secs(90)),
+                order: Some(SlideshowOrder::Mtime),
+                recursive: true,
+                resize: None,
+                tile: TileOptions::default(),
+                placement: Placement::default(),
+                source_transform: SourceTransform::default(),
+                fill_rgb: None,
+                padding: Padding::default(),
+                transition: Some(TransitionKind::Xfd),
+                transition_options: TransitionOptions::default(),
+                ease: None,
+            },
+            current: PathBuf::from("/home/test/wallpapers/b.png"),
+            paused: true,
+        });
 
         let mut buf = vec![];
-        restore.serialize_to(&mut buf).unwrap();
-        let new_restore = super::Restore::deserialize_from(&buf[..]).unwrap();
+        state.serialize_to(&mut buf).unwrap();
+        let new_state = RestoreState::deserialize_from(&buf[..]).unwrap();
+
+        assert_eq!(state, new_state);
+    }
+
+    #[test]
+    fn test_serde_restore_gradient() {
+        let state = RestoreState::new(Restore::new(
+            ImageSource::Gradient(Gradient {
+                kind: GradientKind::Radial,
+                stops: vec![
+                    GradientStop {
+                        rgb: (0x22, 0x44, 0x66),
+                        offset: None,
+                    },
+                    GradientStop {
+                        rgb: (0xff, 0xff, 0xff),
+                        offset: Some(0.8),
+                    },
+                ],
+                angle: 45.0,
+            }),
+            ResizeOption::Stretch,
+            (0, 0, 0),
+        ));
+
+        let mut buf = vec![];
+        state.serialize_to(&mut buf).unwrap();
+        let new_state = RestoreState::deserialize_from(&buf[..]).unwrap();
+
+        assert_eq!(state.wallpaper.source, new_state.wallpaper.source);
+    }
+
+    #[test]
+    fn test_deserialize_msgpack_restore() {
+        // Layout of `Restore` before the restore file was versioned.
+        #[derive(Serialize)]
+        struct MsgpackRestore {
+            source: ImageSource,
+            resize_option: ResizeOption,
+            fill_rgb: (u8, u8, u8),
+            fill: Fill,
+            tile: TileOptions,
+            placement: Placement,
+            source_transform: SourceTransform,
+        }
+
+        let msgpack = MsgpackRestore {
+            source: ImageSource::Path(PathBuf::from("/home/test/test.jpg")),
+            resize_option: ResizeOption::Fit,
+            fill_rgb: (1, 2, 3),
+            fill: Fill::Blur,
+            tile: TileOptions::default(),
+            placement: Placement::default(),
+            source_transform: SourceTransform {
+                crop: None,
+                rotate: Some(Rotation::Rotate180),
+                flip: None,
+            },
+        };
+
+        let mut buf = vec![];
+        msgpack
+            .serialize(&mut rmp_serde::Serializer::new(&mut buf))
+            .unwrap();
+        let state = RestoreState::deserialize_from(&buf[..]).unwrap();
+
+        assert_eq!(state.version, super::RESTORE_VERSION);
+        assert_eq!(state.wallpaper.source, msgpack.source);
+        assert_eq!(state.wallpaper.fill, msgpack.fill);
+        assert_eq!(state.wallpaper.source_transform, msgpack.source_transform);
+        assert_eq!(state.wallpaper.filters, None);
+        assert!(state.outputs.is_empty());
+    }
+
+    #[test]
+    fn test_deserialize_legacy_restore() {
+        #[derive(Serialize)]
+        struct LegacyRestore {
+            file_path: PathBuf,
+            resize_option: ResizeOption,
+            fill_rgb: (u8, u8, u8),
+        }
+
+        let legacy = LegacyRestore {
+            file_path: PathBuf::from("/home/test/test.jpg"),
+            resize_option: ResizeOption::Fit,
+            fill_rgb: (1, 2, 3),
+        };
+
+        let mut buf = vec![];
+        legacy
+            .serialize(&mut rmp_serde::Serializer::new(&mut buf))
+            .unwrap();
+        let restore = RestoreState::deserialize_from(&buf[..]).unwrap().wallpaper;
+
+        assert_eq!(restore.source, ImageSource::Path(legacy.file_path));
+        assert_eq!(restore.resize_option, legacy.resize_option);
+        assert_eq!(restore.fill_rgb, legacy.fill_rgb);
+    }
+
+    #[test]
+    fn test_deserialize_newer_restore() {
+        let state = RestoreState::new(Restore::new(
+            ImageSource::Color((1, 2, 3)),
+            ResizeOption::Stretch,
+            (1, 2, 3),
+        ));
+        let mut json = serde_json::to_value(&state).unwrap();
+        json["version"] = (super::RESTORE_VERSION + 1).into();
+        json["new-field"] = true.into();
 
-        assert_eq!(restore.file_path, new_restore.file_path);
-        assert_eq!(restore.resize_option, new_restore.resize_option);
-        assert_eq!(restore.fill_rgb, new_restore.fill_rgb);
+        let buf = serde_json::to_vec(&json).unwrap();
+        let e = RestoreState::deserialize_from(&buf[..]).unwrap_err();
+        assert!(e.to_string().contains("newer version"), "{e}");
     }
 }
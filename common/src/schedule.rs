@@ -0,0 +1,670 @@
+use crate::{
+    cli::{
+        client::{
+            DEFAULT_GRADIENT_ANGLE, DEFAULT_GRADIENT_KIND, Fill, Gradient, GradientKind,
+            GradientStop, MAX_GRADIENT_STOPS, Padding, Placement, SourceTransform, TileOptions,
+            TransitionKind, TransitionOptions, parse_gradient_stop,
+        },
+        server::ResizeOption,
+    },
+    config::{rgb, value_enum},
+    ipc::{ImageArgs, ImageSource},
+};
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone};
+use serde::{Deserialize, Deserializer};
+use std::{path::PathBuf, time::Duration};
+
+/// Transitions of GNOME backgrounds blend the images for their whole duration, which is often
+/// hours. They are rendered as cross fades of at most this long instead, in seconds.
+pub const MAX_GNOME_TRANSITION_DURATION: f64 = 60.0;
+
+/// The `[schedule]` section of the config file.
+#[derive(Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ScheduleConfig {
+    /// Where sunrise and sunset are computed for, in degrees. North and east are positive.
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    /// GNOME `<background>` XML, e.g. from `/usr/share/backgrounds`. Used instead of `entry`.
+    pub file: Option<PathBuf>,
+    /// Wallpapers by the time of day they are displayed from, e.g. `[[schedule.entry]]`.
+    pub entry: Vec<ScheduleEntry>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ScheduleEntry {
+    /// `HH:MM[:SS]`, or `sunrise`/`sunset` with an optional offset, e.g. `sunset-30m`.
+    #[serde(deserialize_with = "time_of_day")]
+    pub at: TimeOfDay,
+    /// Only one of `image`, `color` and `gradient` can be set.
+    pub image: Option<PathBuf>,
+    #[serde(default, deserialize_with = "rgb")]
+    pub color: Option<(u8, u8, u8)>,
+    /// Color stops in the form of `RRGGBB[@OFFSET]`, like `pwww gradient`.
+    #[serde(default, deserialize_with = "gradient_stops")]
+    pub gradient: Option<Vec<GradientStop>>,
+    #[serde(default, deserialize_with = "value_enum")]
+    pub gradient_kind: Option<GradientKind>,
+    pub gradient_angle: Option<f64>,
+    /// Transition into this wallpaper. Options which are not set fall back to the defaults.
+    #[serde(default, deserialize_with = "value_enum")]
+    pub transition: Option<TransitionKind>,
+    pub transition_duration: Option<f64>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TimeOfDay {
+    Time(NaiveTime),
+    /// Sunrise, plus an offset in seconds.
+    Sunrise(i64),
+    /// Sunset, plus an offset in seconds.
+    Sunset(i64),
+}
+
+/// A wallpaper decided by the schedule, and the transition into it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScheduledWallpaper {
+    pub image: ImageSource,
+    pub transition: Option<TransitionKind>,
+    pub transition_duration: Option<f64>,
+}
+
+/// Wallpapers changing with the time of day.
+#[derive(Clone, Debug)]
+pub enum Schedule {
+    Daily {
+        entries: Vec<(TimeOfDay, ScheduledWallpaper)>,
+        /// Latitude and longitude, which are needed if any entry is at sunrise or sunset.
+        coordinates: Option<(f64, f64)>,
+    },
+    Gnome(GnomeBackground),
+}
+
+/// A GNOME `<background>`, which loops through its slots from its start time.
+#[derive(Clone, Debug)]
+pub struct GnomeBackground {
+    start: NaiveDateTime,
+    slots: Vec<GnomeSlot>,
+}
+
+#[derive(Clone, Debug)]
+struct GnomeSlot {
+    /// In seconds.
+    duration: f64,
+    /// The image displayed during a `<static>` slot, or faded to by a `<transition>` slot.
+    image: PathBuf,
+    transition: bool,
+}
+
+impl Schedule {
+    /// The schedule of the config section. The GNOME background file is read, so call this outside
+    /// the event loop.
+    pub fn load(config: &ScheduleConfig) -> Result<Self> {
+        if let Some(path) = &config.file {
+            let content = std::fs::read_to_string(path)
+                .map_err(|e| anyhow!("Cannot read GNOME background `{}`: {e}", path.display()))?;
+            return GnomeBackground::parse(&content)
+                .map(Self::Gnome)
+                .map_err(|e| anyhow!("Invalid GNOME background `{}`: {e}", path.display()));
+        }
+
+        if config.entry.is_empty() {
+            return Err(anyhow!("The schedule has no entry"));
+        }
+
+        let coordinates = config.latitude.zip(config.longitude);
+        let entries = config
+            .entry
+            .iter()
+            .map(|entry| Ok((entry.at, entry.wallpaper()?)))
+            .collect::<Result<Vec<_>>>()?;
+        if coordinates.is_none()
+            && entries
+                .iter()
+                .any(|(at, _)| !matches!(at, TimeOfDay::Time(_)))
+        {
+            return Err(anyhow!(
+                "`latitude` and `longitude` are needed for sunrise and sunset"
+            ));
+        }
+
+        Ok(Self::Daily {
+            entries,
+            coordinates,
+        })
+    }
+
+    /// The wallpaper scheduled for now, and how long until the next one.
+    pub fn now(&self) -> Option<(ScheduledWallpaper, Duration)> {
+        self.at(&Local::now())
+    }
+
+    /// The wallpaper scheduled at `now`, and how long until the next one. `None` if no entry can be
+    /// placed around `now`, e.g. only sunrise entries during a polar night.
+    pub fn at<Tz: TimeZone>(&self, now: &DateTime<Tz>) -> Option<(ScheduledWallpaper, Duration)> {
+        match self {
+            Self::Daily {
+                entries,
+                coordinates,
+            } => {
+                // Entries of yesterday are still displayed before the first entry of today.
+                let today = now.date_naive();
+                let mut times = [today.pred_opt()?, today, today.succ_opt()?]
+                    .into_iter()
+                    .flat_map(|date| {
+                        entries.iter().filter_map(move |(at, wallpaper)| {
+                            Some((at.on(date, &now.timezone(), *coordinates)?, wallpaper))
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                times.sort_by(|a, b| a.0.cmp(&b.0));
+
+                let next = times.iter().position(|(time, _)| time > now)?;
+                let (_, wallpaper) = times.get(next.checked_sub(1)?)?;
+                let remaining = (times[next].0.clone() - now.clone()).to_std().ok()?;
+                Some(((*wallpaper).clone(), remaining))
+            }
+            Self::Gnome(background) => background.at(now.naive_local()),
+        }
+    }
+}
+
+impl ScheduleEntry {
+    fn wallpaper(&self) -> Result<ScheduledWallpaper> {
+        let image = match (&self.image, self.color, &self.gradient) {
+            (Some(path), None, None) => ImageSource::Path(path.clone()),
+            (None, Some(rgb), None) => ImageSource::Color(rgb),
+            (None, None, Some(stops)) => ImageSource::Gradient(Gradient {
+                kind: self.gradient_kind.unwrap_or(DEFAULT_GRADIENT_KIND),
+                stops: stops.clone(),
+                angle: self.gradient_angle.unwrap_or(DEFAULT_GRADIENT_ANGLE),
+            }),
+            _ => {
+                return Err(anyhow!(
+                    "Exactly one of `image`, `color` and `gradient` must be set at `{:?}`",
+                    self.at
+                ));
+            }
+        };
+
+        Ok(ScheduledWallpaper {
+            image,
+            transition: self.transition,
+            transition_duration: self.transition_duration,
+        })
+    }
+}
+
+impl ScheduledWallpaper {
+    /// The request displaying this wallpaper on all outputs. Procedural wallpapers cover the whole
+    /// screen, like `pwww color` and `pwww gradient`.
+    pub fn image_args(&self) -> ImageArgs {
+        let (resize, fill_rgb, padding) = match &self.image {
+            ImageSource::Color(rgb) => (
+                Some(ResizeOption::Stretch),
+                Some(*rgb),
+                Padding::from(Fill::Color),
+            ),
+            ImageSource::Gradient(_) => (
+                Some(ResizeOption::Stretch),
+                None,
+                Padding::from(Fill::Color),
+            ),
+            _ => (None, None, Padding::default()),
+        };
+
+        ImageArgs {
+            image: self.image.clone(),
+            outputs: vec![],
+            resize,
+            tile: TileOptions::default(),
+            placement: Placement::default(),
+            source_transform: SourceTransform::default(),
+            fill_rgb,
+            padding,
+            transition: self.transition,
+            transition_options: TransitionOptions {
+                duration: self.transition_duration,
+                ..TransitionOptions::default()
+            },
+            ease: None,
+        }
+    }
+}
+
+impl TimeOfDay {
+    /// When it is on `date` in `tz`. `None` if it doesn't happen on that day, e.g. in a gap of
+    /// daylight saving time or if the sun doesn't rise.
+    fn on<Tz: TimeZone>(
+        &self,
+        date: NaiveDate,
+        tz: &Tz,
+        coordinates: Option<(f64, f64)>,
+    ) -> Option<DateTime<Tz>> {
+        let (sun_time, offset) = match *self {
+            Self::Time(time) => return tz.from_local_datetime(&date.and_time(time)).earliest(),
+            Self::Sunrise(offset) => (sun_times(date, coordinates?)?.0, offset),
+            Self::Sunset(offset) => (sun_times(date, coordinates?)?.1, offset),
+        };
+        let time = sun_time.checked_add_signed(chrono::TimeDelta::seconds(offset))?;
+        Some(time.with_timezone(tz))
+    }
+
+    fn parse(s: &str) -> Result<Self> {
+        let (sun, rest) = if let Some(rest) = s.strip_prefix("sunrise") {
+            (Self::Sunrise as fn(i64) -> Self, rest)
+        } else if let Some(rest) = s.strip_prefix("sunset") {
+            (Self::Sunset as fn(i64) -> Self, rest)
+        } else {
+            return NaiveTime::parse_from_str(s, "%H:%M:%S")
+                .or_else(|_| NaiveTime::parse_from_str(s, "%H:%M"))
+                .map(Self::Time)
+                .map_err(|e| anyhow!("Failed to parse time of day `{s}`: {e}"));
+        };
+
+        let offset = match rest.trim().split_at_checked(1) {
+            None => 0,
+            Some((sign, offset)) => {
+                let offset = humantime::parse_duration(offset.trim())
+                    .map_err(|e| anyhow!("Failed to parse offset of `{s}`: {e}"))?
+                    .as_secs() as i64;
+                match sign {
+                    "+" => offset,
+                    "-" => -offset,
+                    _ => return Err(anyhow!("Offset of `{s}` must start with `+` or `-`")),
+                }
+            }
+        };
+
+        Ok(sun(offset))
+    }
+}
+
+/// Sunrise and sunset on `date` at `(latitude, longitude)`, by the sunrise equation. `None` during
+/// polar days and nights.
+pub fn sun_times(
+    date: NaiveDate,
+    (latitude, longitude): (f64, f64),
+) -> Option<(DateTime<chrono::Utc>, DateTime<chrono::Utc>)> {
+    const J2000: f64 = 2451545.0;
+    const UNIX_EPOCH_JULIAN_DAY: f64 = 2440587.5;
+
+    let days = date
+        .signed_duration_since(NaiveDate::from_ymd_opt(2000, 1, 1)?)
+        .num_days() as f64;
+    let mean_solar_time = days + 0.0008 - longitude / 360.0;
+    let mean_anomaly = (357.5291 + 0.98560028 * mean_solar_time).rem_euclid(360.0);
+    let m = mean_anomaly.to_radians();
+    let center = 1.9148 * m.sin() + 0.02 * (2.0 * m).sin() + 0.0003 * (3.0 * m).sin();
+    let ecliptic_longitude = (mean_anomaly + center + 180.0 + 102.9372)
+        .rem_euclid(360.0)
+        .to_radians();
+    let transit =
+        J2000 + mean_solar_time + 0.0053 * m.sin() - 0.0069 * (2.0 * ecliptic_longitude).sin();
+
+    let declination = (ecliptic_longitude.sin() * 23.4397_f64.to_radians().sin()).asin();
+    let latitude = latitude.to_radians();
+    let cos_hour_angle = ((-0.833_f64).to_radians().sin() - latitude.sin() * declination.sin())
+        / (latitude.cos() * declination.cos());
+    if !(-1.0..=1.0).contains(&cos_hour_angle) {
+        return None;
+    }
+    let hour_angle = cos_hour_angle.acos().to_degrees();
+
+    let to_utc = |julian_day: f64| {
+        let millis = ((julian_day - UNIX_EPOCH_JULIAN_DAY) * 86_400_000.0).round() as i64;
+        DateTime::from_timestamp_millis(millis)
+    };
+    Some((
+        to_utc(transit - hour_angle / 360.0)?,
+        to_utc(transit + hour_angle / 360.0)?,
+    ))
+}
+
+impl GnomeBackground {
+    pub fn parse(xml: &str) -> Result<Self> {
+        let document = roxmltree::Document::parse(xml)?;
+        let background = document.root_element();
+        if !background.has_tag_name("background") {
+            return Err(anyhow!("The root element is not `<background>`"));
+        }
+
+        let mut start = None;
+        let mut slots = vec![];
+        for node in background.children().filter(roxmltree::Node::is_element) {
+            match node.tag_name().name() {
+                "starttime" => start = Some(parse_start_time(node)?),
+                "static" => slots.push(GnomeSlot {
+                    duration: parse_duration(node)?,
+                    image: child_file(node, "file")?,
+                    transition: false,
+                }),
+                "transition" => slots.push(GnomeSlot {
+                    duration: parse_duration(node)?,
+                    image: child_file(node, "to")?,
+                    transition: true,
+                }),
+                _ => {}
+            }
+        }
+
+        if slots.iter().map(|slot| slot.duration).sum::<f64>() <= 0.0 {
+            return Err(anyhow!(
+                "The background has no `<static>` or `<transition>`"
+            ));
+        }
+
+        Ok(Self {
+            // Backgrounds without a start time loop from midnight.
+            start: start.unwrap_or_default(),
+            slots,
+        })
+    }
+
+    fn at(&self, now: NaiveDateTime) -> Option<(ScheduledWallpaper, Duration)> {
+        let total = self.slots.iter().map(|slot| slot.duration).sum::<f64>();
+        let mut elapsed = ((now - self.start).num_milliseconds() as f64 / 1000.0).rem_euclid(total);
+        for slot in &self.slots {
+            if elapsed < slot.duration {
+                let wallpaper = ScheduledWallpaper {
+                    image: ImageSource::Path(slot.image.clone()),
+                    transition: slot.transition.then_some(TransitionKind::Xfd),
+                    transition_duration: slot
+                        .transition
+                        .then_some(slot.duration.min(MAX_GNOME_TRANSITION_DURATION)),
+                };
+                let remaining = Duration::try_from_secs_f64(slot.duration - elapsed).ok()?;
+                return Some((wallpaper, remaining));
+            }
+            elapsed -= slot.duration;
+        }
+
+        None
+    }
+}
+
+fn child_text<'a>(node: roxmltree::Node<'a, '_>, name: &str) -> Result<&'a str> {
+    node.children()
+        .find(|child| child.has_tag_name(name))
+        .and_then(|child| child.text())
+        .map(str::trim)
+        .ok_or(anyhow!("`<{}>` has no `<{name}>`", node.tag_name().name()))
+}
+
+fn parse_duration(node: roxmltree::Node) -> Result<f64> {
+    let text = child_text(node, "duration")?;
+    let duration = text
+        .parse::<f64>()
+        .map_err(|e| anyhow!("Failed to parse duration `{text}`: {e}"))?;
+    if !duration.is_finite() || duration <= 0.0 {
+        return Err(anyhow!(
+            "Duration `{text}` must be a number of seconds greater than zero"
+        ));
+    }
+
+    Ok(duration)
+}
+
+/// The file in `<name>`, which may list the image in several sizes, e.g.
+/// `<size width="1920" height="1080">...</size>`. The largest one is picked.
+fn child_file(node: roxmltree::Node, name: &str) -> Result<PathBuf> {
+    let file = node
+        .children()
+        .find(|child| child.has_tag_name(name))
+        .ok_or(anyhow!("`<{}>` has no `<{name}>`", node.tag_name().name()))?;
+
+    let area = |size: &roxmltree::Node| {
+        let dimension = |name| size.attribute(name).and_then(|v| v.parse::<u64>().ok());
+        dimension("width").unwrap_or(0) * dimension("height").unwrap_or(0)
+    };
+    let path = match file
+        .children()
+        .filter(|child| child.has_tag_name("size"))
+        .max_by_key(area)
+    {
+        Some(size) => size.text(),
+        None => file.text(),
+    };
+
+    path.map(str::trim)
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+        .ok_or(anyhow!("`<{name}>` has no path"))
+}
+
+fn parse_start_time(node: roxmltree::Node) -> Result<NaiveDateTime> {
+    let field = |name| {
+        let value = child_text(node, name)?;
+        value
+            .parse::<u32>()
+            .map_err(|e| anyhow!("Failed to parse `<{name}>` of `<starttime>`: {e}"))
+    };
+
+    let date = NaiveDate::from_ymd_opt(field("year")? as i32, field("month")?, field("day")?);
+    let time = NaiveTime::from_hms_opt(field("hour")?, field("minute")?, field("second")?);
+    date.zip(time)
+        .map(|(date, time)| date.and_time(time))
+        .ok_or(anyhow!("Invalid `<starttime>`"))
+}
+
+fn time_of_day<'de, D: Deserializer<'de>>(deserializer: D) -> Result<TimeOfDay, D::Error> {
+    let s = String::deserialize(deserializer)?;
+    TimeOfDay::parse(&s).map_err(serde::de::Error::custom)
+}
+
+fn gradient_stops<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Vec<GradientStop>>, D::Error> {
+    let stops = Vec::<String>::deserialize(deserializer)?;
+    if stops.is_empty() {
+        return Err(serde::de::Error::custom(
+            "a gradient needs at least one stop",
+        ));
+    }
+    if stops.len() > MAX_GRADIENT_STOPS {
+        return Err(serde::de::Error::custom(format!(
+            "a gradient can have at most {MAX_GRADIENT_STOPS} stops, but {} are given",
+            stops.len()
+        )));
+    }
+
+    stops
+        .iter()
+        .map(|stop| parse_gradient_stop(stop))
+        .collect::<Result<Vec<_>>>()
+        .map(Some)
+        .map_err(serde::de::Error::custom)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{GnomeBackground, Schedule, ScheduleConfig, TimeOfDay, sun_times};
+    use crate::{cli::client::TransitionKind, ipc::ImageSource};
+    use chrono::{FixedOffset, NaiveDate, NaiveTime, TimeZone, Utc};
+    use std::{path::PathBuf, time::Duration};
+
+    #[test]
+    fn test_sun_times() {
+        // Berlin on the summer solstice: sunrise at 04:43 and sunset at 21:33, CEST.
+        let date = NaiveDate::from_ymd_opt(2024, 6, 21).unwrap();
+        let (sunrise, sunset) = sun_times(date, (52.52, 13.405)).unwrap();
+        let expected_sunrise = Utc.with_ymd_and_hms(2024, 6, 21, 2, 43, 0).unwrap();
+        let expected_sunset = Utc.with_ymd_and_hms(2024, 6, 21, 19, 33, 0).unwrap();
+        assert!((sunrise - expected_sunrise).num_minutes().abs() <= 3);
+        assert!((sunset - expected_sunset).num_minutes().abs() <= 3);
+
+        // The sun doesn't rise during the polar night.
+        let date = NaiveDate::from_ymd_opt(2024, 12, 21).unwrap();
+        assert!(sun_times(date, (78.22, 15.65)).is_none());
+    }
+
+    #[test]
+    fn test_daily_schedule() {
+        let config: ScheduleConfig = toml::from_str(
+            r#"
+            latitude = 52.52
+            longitude = 13.405
+
+            [[entry]]
+            at = "sunrise+30m"
+            image = "/day.png"
+
+            [[entry]]
+            at = "12:00"
+            color = "336699"
+            transition = "xfd"
+            transition-duration = 10
+
+            [[entry]]
+            at = "sunset"
+            gradient = ["000020", "000000@1"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.entry[0].at, TimeOfDay::Sunrise(30 * 60));
+        let schedule = Schedule::load(&config).unwrap();
+
+        let cest = FixedOffset::east_opt(2 * 3600).unwrap();
+        let at = |hour, minute| {
+            let now = cest.with_ymd_and_hms(2024, 6, 21, hour, minute, 0).unwrap();
+            schedule.at(&now).unwrap()
+        };
+
+        // The gradient of last night is displayed until 30 minutes after sunrise.
+        let (wallpaper, remaining) = at(3, 0);
+        assert!(matches!(wallpaper.image, ImageSource::Gradient(_)));
+        assert!((remaining.as_secs() as i64 - (2 * 3600 + 13 * 60)).abs() <= 180);
+
+        let (wallpaper, remaining) = at(11, 0);
+        assert_eq!(
+            wallpaper.image,
+            ImageSource::Path(PathBuf::from("/day.png"))
+        );
+        assert_eq!(remaining, Duration::from_secs(3600));
+
+        let (wallpaper, _) = at(12, 0);
+        assert_eq!(wallpaper.image, ImageSource::Color((0x33, 0x66, 0x99)));
+        assert_eq!(wallpaper.transition, Some(TransitionKind::Xfd));
+        assert_eq!(
+            wallpaper.image_args().transition_options.duration,
+            Some(10.0)
+        );
+
+        let (wallpaper, _) = at(23, 0);
+        assert!(matches!(wallpaper.image, ImageSource::Gradient(_)));
+    }
+
+    #[test]
+    fn test_invalid_schedule() {
+        let config: ScheduleConfig = toml::from_str(
+            r#"
+            [[entry]]
+            at = "sunset"
+            image = "/night.png"
+            "#,
+        )
+        .unwrap();
+        assert!(Schedule::load(&config).is_err());
+
+        let config: ScheduleConfig = toml::from_str(
+            r#"
+            [[entry]]
+            at = "08:00"
+            image = "/day.png"
+            color = "000000"
+            "#,
+        )
+        .unwrap();
+        assert!(Schedule::load(&config).is_err());
+
+        assert!(toml::from_str::<ScheduleConfig>("[[entry]]\nat = \"sunset*30m\"").is_err());
+        assert!(toml::from_str::<ScheduleConfig>("[[entry]]\nat = \"25:00\"").is_err());
+
+        let stops = vec!["\"000000\""; 17].join(", ");
+        let config = format!("[[entry]]\nat = \"08:00\"\ngradient = [{stops}]");
+        assert!(toml::from_str::<ScheduleConfig>(&config).is_err());
+    }
+
+    #[test]
+    fn test_gnome_background() {
+        let background = GnomeBackground::parse(
+            r#"
+            <background>
+              <starttime>
+                <year>2011</year><month>11</month><day>24</day>
+                <hour>7</hour><minute>00</minute><second>00</second>
+              </starttime>
+              <static>
+                <duration>43200.0</duration>
+                <file>/day.jpg</file>
+              </static>
+              <transition type="overlay">
+                <duration>3600.0</duration>
+                <from>/day.jpg</from>
+                <to>/night.jpg</to>
+              </transition>
+              <static>
+                <duration>39600.0</duration>
+                <file>
+                  <size width="1024" height="768">/night-small.jpg</size>
+                  <size width="3840" height="2160">/night.jpg</size>
+                </file>
+              </static>
+            </background>
+            "#,
+        )
+        .unwrap();
+
+        let at = |hour, minute| {
+            let date = NaiveDate::from_ymd_opt(2024, 6, 21).unwrap();
+            background.at(date.and_time(NaiveTime::from_hms_opt(hour, minute, 0).unwrap()))
+        };
+
+        let (wallpaper, remaining) = at(8, 0).unwrap();
+        assert_eq!(
+            wallpaper.image,
+            ImageSource::Path(PathBuf::from("/day.jpg"))
+        );
+        assert_eq!(wallpaper.transition, None);
+        assert_eq!(remaining, Duration::from_secs(11 * 3600));
+
+        let (wallpaper, remaining) = at(19, 30).unwrap();
+        assert_eq!(
+            wallpaper.image,
+            ImageSource::Path(PathBuf::from("/night.jpg"))
+        );
+        assert_eq!(wallpaper.transition, Some(TransitionKind::Xfd));
+        assert_eq!(wallpaper.transition_duration, Some(60.0));
+        assert_eq!(remaining.as_secs(), 30 * 60);
+
+        // The background loops every day.
+        let (wallpaper, remaining) = at(6, 0).unwrap();
+        assert_eq!(
+            wallpaper.image,
+            ImageSource::Path(PathBuf::from("/night.jpg"))
+        );
+        assert_eq!(remaining.as_secs(), 3600);
+
+        assert!(GnomeBackground::parse("<background></background>").is_err());
+        assert!(GnomeBackground::parse("<wallpapers></wallpapers>").is_err());
+
+        let with_duration = |duration| {
+            GnomeBackground::parse(&format!(
+                "<background><static><duration>{duration}</duration>\
+                <file>/day.jpg</file></static></background>"
+            ))
+        };
+        for duration in ["inf", "NaN", "-60", "0"] {
+            assert!(with_duration(duration).is_err(), "{duration}");
+        }
+        // Too long to be a `Duration`, which must not panic.
+        let background = with_duration("1e20").unwrap();
+        let now = NaiveDate::from_ymd_opt(2024, 6, 21)
+            .unwrap()
+            .and_hms_opt(8, 0, 0)
+            .unwrap();
+        assert!(background.at(now).is_none());
+    }
+}
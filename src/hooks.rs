@@ -0,0 +1,99 @@
+use common::config::Hooks;
+use std::{fmt, io, process::Stdio, time::Duration};
+use tokio::{process::Command, task::JoinHandle};
+use tracing::{debug, warn};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum HookEvent {
+    WallpaperChanged,
+    TransitionFinished,
+    OutputAdded,
+    DaemonStarted,
+    DaemonStopped,
+}
+
+impl HookEvent {
+    /// The command configured for this event, if any.
+    fn command(self, hooks: &Hooks) -> Option<&str> {
+        match self {
+            Self::WallpaperChanged => hooks.wallpaper_changed.as_deref(),
+            Self::TransitionFinished => hooks.transition_finished.as_deref(),
+            Self::OutputAdded => hooks.output_added.as_deref(),
+            Self::DaemonStarted => hooks.daemon_started.as_deref(),
+            Self::DaemonStopped => hooks.daemon_stopped.as_deref(),
+        }
+    }
+}
+
+/// The name in `PWWW_EVENT`, which is the same as the option of the hook.
+impl fmt::Display for HookEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::WallpaperChanged => "wallpaper-changed",
+            Self::TransitionFinished => "transition-finished",
+            Self::OutputAdded => "output-added",
+            Self::DaemonStarted => "daemon-started",
+            Self::DaemonStopped => "daemon-stopped",
+        })
+    }
+}
+
+/// Run the hook of `event` with the environment variables `vars`, on a task of its own so that
+/// the event loop isn't blocked by it. `None` if no hook is configured for `event`.
+pub fn run(
+    hooks: &Hooks,
+    event: HookEvent,
+    vars: Vec<(&'static str, String)>,
+) -> Option<JoinHandle<()>> {
+    let command = event.command(hooks)?.to_owned();
+    let timeout = hooks.timeout();
+    Some(tokio::spawn(run_command(command, event, vars, timeout)))
+}
+
+async fn run_command(
+    command: String,
+    event: HookEvent,
+    vars: Vec<(&'static str, String)>,
+    timeout: Duration,
+) {
+    debug!("Running the hook of {event}: `{command}` ...");
+    let mut child = match Command::new("sh")
+        .arg("-c")
+        .arg(&command)
+        .env("PWWW_EVENT", event.to_string())
+        .envs(vars)
+        .stdin(Stdio::null())
+        // The commands run by `sh` are killed along with it on timeout, as a process group.
+        .process_group(0)
+        .kill_on_drop(true)
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(e) => {
+            warn!("Failed to run the hook of {event}: {e}");
+            return;
+        }
+    };
+
+    match tokio::time::timeout(timeout, child.wait()).await {
+        Ok(Ok(status)) if status.success() => debug!("The hook of {event} is finished"),
+        Ok(Ok(status)) => warn!("The hook of {event} failed: {status}"),
+        Ok(Err(e)) => warn!("Failed to wait for the hook of {event}: {e}"),
+        Err(_) => {
+            warn!("The hook of {event} timed out after {timeout:?}, killing it ...");
+            if let Some(pid) = child.id() {
+                // SAFETY: `kill` doesn't touch any memory. The group is led by `sh`, which isn't
+                // reaped yet, so its ID cannot be reused by another group.
+                if unsafe { libc::kill(-(pid as libc::pid_t), libc::SIGKILL) } != 0 {
+                    warn!(
+                        "Failed to kill the hook of {event}: {}",
+                        io::Error::last_os_error()
+                    );
+                }
+            }
+            if let Err(e) = child.kill().await {
+                warn!("Failed to kill the hook of {event}: {e}");
+            }
+        }
+    }
+}
@@ -1,27 +1,36 @@
 mod ease;
+mod hooks;
 mod server;
+mod slideshow;
 mod wallpaper;
+mod watcher;
 
 use anyhow::{Result, anyhow};
 use clap::{CommandFactory, Parser};
-use common::cli::{client::TransitionKind, server as server_cli};
-use common::ipc::{self, ImageArgs};
-use common::restore::Restore;
-use common::utils;
-use std::sync::Arc;
+use common::cli::{
+    client::{Fill, Padding},
+    server as server_cli,
+};
+use common::config::Config;
+use common::history::History;
+use common::ipc::{self, ImageSource};
+use common::restore::RestoreState;
+use std::{collections::HashMap, sync::Arc};
 use tokio::{
     net::UnixStream,
     select,
     signal::unix::SignalKind,
     sync::{mpsc, oneshot},
 };
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 use wayland_client::QueueHandle;
 use wayland_client::{Connection, globals::registry_queue_init};
 
 use crate::{
+    hooks::HookEvent,
     server::{Server, TaskHandle, TaskHub},
-    wallpaper::Wallpaper,
+    wallpaper::{ContentArgs, Wallpaper},
+    watcher::FileWatcher,
 };
 
 const REQUSET_BUFFER_SIZE: usize = 4;
@@ -33,7 +42,7 @@ async fn main() -> Result<()> {
     let args = server_cli::Args::parse();
     let mut builder = wallpaper::WallpaperBuilder::new();
 
-    if let server_cli::ServerSubcommand::Completion { shell } = args.subcommand {
+    if let Some(server_cli::ServerSubcommand::Completion { shell }) = args.subcommand {
         let mut command = server_cli::Args::command();
         let name = command.get_name().to_string();
         common::cli::clap_complete::generate(shell, &mut command, name, &mut std::io::stdout());
@@ -41,71 +50,143 @@ async fn main() -> Result<()> {
         return Ok(());
     }
 
-    let (image_path, resize, fill_rgb) = match args.subcommand {
-        server_cli::ServerSubcommand::FromPath {
+    // The daemon comes up anyway, so that the config file can be fixed and reloaded.
+    let config = Config::load().unwrap_or_else(|e| {
+        error!("Failed to load the config file, using the defaults: {e}");
+        Config::default()
+    });
+    let mut output_contents = HashMap::new();
+    let mut slideshow = None;
+    // Wallpapers given on the command line take precedence over the schedule.
+    let schedule = matches!(
+        args.subcommand,
+        None | Some(server_cli::ServerSubcommand::Restore)
+    );
+    let content = match args.subcommand {
+        None => None,
+        Some(server_cli::ServerSubcommand::FromPath {
             path,
             resize,
+            tile,
+            placement,
+            source_transform,
             fill_rgb,
-        } => {
-            let resize = if resize.no_resize {
-                server_cli::ResizeOption::No
-            } else {
-                resize.resize.unwrap_or(server_cli::DEFAULT_RESIZE)
-            };
-
-            (path, resize, fill_rgb.unwrap_or(server_cli::RGB))
-        }
-        server_cli::ServerSubcommand::Restore => {
-            let restore_path = server_cli::default_restore_path()?;
-            let content = tokio::fs::read(restore_path).await?;
-            let Restore {
-                file_path,
-                resize_option,
-                fill_rgb,
-            } = Restore::deserialize_from(&content[..])?;
-
-            (file_path, resize_option, fill_rgb)
-        }
-        server_cli::ServerSubcommand::Completion { shell: _ } => {
+            padding,
+            transition,
+            transition_options,
+            ease,
+        }) => Some(ContentArgs {
+            resize: resize.into_option(),
+            tile,
+            placement,
+            source_transform,
+            fill_rgb,
+            padding,
+            transition: transition.into_option(),
+            transition_options,
+            ease: ease.into_option(),
+            ..ContentArgs::new(ImageSource::Path(path))
+        }),
+        Some(server_cli::ServerSubcommand::Color { rgb }) => Some(ContentArgs {
+            resize: Some(server_cli::ResizeOption::Stretch),
+            fill_rgb: Some(rgb),
+            padding: Padding::from(Fill::Color),
+            ..ContentArgs::new(ImageSource::Color(rgb))
+        }),
+        Some(server_cli::ServerSubcommand::Gradient { gradient }) => Some(ContentArgs {
+            resize: Some(server_cli::ResizeOption::Stretch),
+            padding: Padding::from(Fill::Color),
+            ..ContentArgs::new(ImageSource::Gradient(gradient.into_gradient()?))
+        }),
+        Some(server_cli::ServerSubcommand::Restore) => match read_restore_state().await {
+            Ok(state) => {
+                slideshow = state.slideshow;
+                output_contents = state
+                    .outputs
+                    .into_iter()
+                    .map(|(name, restore)| (name, ContentArgs::from(restore)))
+                    .collect();
+
+                Some(ContentArgs::from(state.wallpaper))
+            }
+            // Fall back to the default image, or the fill color if there isn't any.
+            Err(e) => {
+                warn!("Failed to read the restore file: {e}");
+                config
+                    .default_image
+                    .clone()
+                    .map(|path| ContentArgs::new(ImageSource::Path(path)))
+            }
+        },
+        Some(server_cli::ServerSubcommand::Completion { shell: _ }) => {
             panic!("`completion` is not a valid subcommand");
         }
     };
 
-    builder = builder.with_img_path(image_path);
-    builder = builder.with_resize_option(resize);
-
-    let rgb_u8 = fill_rgb;
-    let rgb_f64 = utils::rgb_u8_to_f64(rgb_u8);
-    builder = builder.with_fill_color(rgb_f64);
+    let uds_path = config.socket_path()?;
+    builder = builder.with_surface_options(&args.surface)?;
+    if let Some(content) = content {
+        builder = builder.with_content(content);
+    }
+    builder = builder.with_output_contents(output_contents);
+    builder = builder.with_slideshow(slideshow);
+    builder = builder.with_history(read_history().await);
+    builder = builder.with_schedule(schedule);
+    let (watch_tx, mut watch_rx) = mpsc::unbounded_channel();
+    match FileWatcher::new(watch_tx) {
+        Ok(file_watcher) => builder = builder.with_file_watcher(file_watcher),
+        Err(e) => warn!("Failed to create the file watcher, files are not watched: {e}"),
+    }
+    builder = builder.with_config(config);
 
     let conn = Connection::connect_to_env()?;
     let (globals, mut event_queue) = registry_queue_init(&conn)?;
     let qh = event_queue.handle();
-    let mut wallpaper = builder
-        .build(&conn, &globals, &qh, Option::<String>::None)
-        .await?;
+    let mut wallpaper = builder.build(&globals, &qh, Option::<String>::None).await?;
 
     debug!("Trying to build the server ...");
-    let (server, server_handle) = Server::new(common::ipc::default_uds_path()?)?;
+    let (server, server_handle) = Server::new(uds_path)?;
     let task_hub = Arc::new(TaskHub::new());
+    // Running transitions keep `task_hub` busy, so the messages controlling them are handled
+    // through a hub of their own.
+    let transition_hub = Arc::new(TaskHub::new());
     let (request_tx, mut request_rx) = mpsc::channel(REQUSET_BUFFER_SIZE);
 
-    let server_join_handle = server.run(move |socket, _addr| {
+    let server_join_handle = server.run(move |mut socket, _addr| {
         let task_hub = task_hub.clone();
+        let transition_hub = transition_hub.clone();
         let request_tx = request_tx.clone();
 
         async move {
-            match task_hub.exclusively_exec(
-                |task_handle, socket| async {
-                    match process_connection(task_handle, socket, request_tx).await {
-                        Ok(_) => debug!("Completed the task"),
-                        Err(e) => error!("Failed to complete the task: {e}"),
-                    }
-                },
-                socket,
-            ) {
+            let message = match ipc::Message::async_receive(&mut socket).await {
+                Ok(message) => message,
+                Err(e) => {
+                    error!("Failed to receive the message: {e}");
+                    return;
+                }
+            };
+
+            let task = |task_handle, (socket, message)| async move {
+                match process_connection(task_handle, socket, message, request_tx).await {
+                    Ok(_) => debug!("Completed the task"),
+                    Err(e) => error!("Failed to complete the task: {e}"),
+                }
+            };
+
+            // Queries only read the state of the daemon, and the daemon can always be killed, so
+            // they don't wait for other requests.
+            if message.is_query() || matches!(message, ipc::Message::Kill) {
+                return task(TaskHandle::unshared(), (socket, message)).await;
+            }
+
+            let hub = if message.controls_transition() {
+                transition_hub
+            } else {
+                task_hub
+            };
+            match hub.exclusively_exec(task, (socket, message)) {
                 Ok(fut) => fut.await,
-                Err((e, mut socket)) => {
+                Err((e, (mut socket, _))) => {
                     error!("{e}");
                     if let Err(e) = ipc::Reply::Error(format!("{e}"))
                         .async_send(&mut socket)
@@ -119,8 +200,19 @@ async fn main() -> Result<()> {
     });
 
     let mut shutdown_sig = wait_shutdown_sig().await?;
+    let mut reload_sig = wait_reload_sig()?;
+
+    // Map the surfaces with the fill color: the first roundtrip advertises the outputs, and the
+    // second one configures their surfaces. Then fade the wallpaper in.
+    event_queue.roundtrip(&mut wallpaper)?;
+    event_queue.roundtrip(&mut wallpaper)?;
+    wallpaper.fade_in(&qh).await;
+    wallpaper.run_hook(HookEvent::DaemonStarted, None);
 
     loop {
+        // Outputs plugged in during the last dispatch get their own wallpapers.
+        wallpaper.show_pending_outputs(&qh).await;
+
         // Flush the outgoing buffers to ensure that the server does receive the messages we've
         // sent.
         event_queue.flush()?;
@@ -147,6 +239,9 @@ async fn main() -> Result<()> {
         // through Unix domain socket), use the `select!` macro to wait for multiple sources.
         let fd = read_guard.connection_fd();
         let fd = tokio::io::unix::AsyncFd::new(fd)?;
+        let slideshow_deadline = wallpaper.slideshow_deadline();
+        let schedule_deadline = wallpaper.schedule_deadline();
+        let watch_deadline = wallpaper.watch_deadline();
         select! {
             _ = fd.readable() => {
                 // `fd` borrows `read_guard`. To complete the read action, explicitly drop
@@ -179,6 +274,44 @@ async fn main() -> Result<()> {
                     }
                 }
             }
+            _ = async {
+                match slideshow_deadline {
+                    Some(deadline) => tokio::time::sleep_until(deadline).await,
+                    None => std::future::pending().await,
+                }
+            } => {
+                wallpaper.advance_slideshow(&qh).await;
+            }
+            _ = async {
+                match schedule_deadline {
+                    Some(deadline) => tokio::time::sleep_until(deadline).await,
+                    None => std::future::pending().await,
+                }
+            } => {
+                wallpaper.advance_schedule(&qh).await;
+            }
+            Some(event) = watch_rx.recv() => {
+                wallpaper.file_changed(event);
+            }
+            _ = async {
+                match watch_deadline {
+                    Some(deadline) => tokio::time::sleep_until(deadline).await,
+                    None => std::future::pending().await,
+                }
+            } => {
+                wallpaper.apply_file_changes(&qh).await;
+            }
+            Some(_) = reload_sig.recv() => {
+                info!("Received a reload signal, reloading the config file ...");
+                match Config::load() {
+                    Ok(config) => {
+                        for option in wallpaper.reload(&qh, config).await {
+                            warn!("Not applied until pwwwd restarts: {option}");
+                        }
+                    }
+                    Err(e) => error!("Failed to reload the config file: {e}"),
+                }
+            }
             maybe_signal = &mut shutdown_sig => {
                 match maybe_signal {
                     Ok(_) => info!("Received a shutdown signal, stopping ..."),
@@ -188,10 +321,13 @@ async fn main() -> Result<()> {
                 break
             }
         }
+    }
 
-        if wallpaper.exited {
-            break;
-        }
+    // The hook is killed by its timeout if it doesn't finish.
+    if let Some(hook) = wallpaper.run_hook(HookEvent::DaemonStopped, None)
+        && let Err(e) = hook.await
+    {
+        error!("Failed to run the hook of daemon-stopped: {e}");
     }
 
     server_handle
@@ -203,12 +339,45 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
+/// Read the wallpaper history. A new one is started if it cannot be read.
+async fn read_history() -> History {
+    let read = async {
+        let content = tokio::fs::read(server_cli::default_history_path()?).await?;
+        History::deserialize_from(&content[..])
+    };
+
+    match read.await {
+        Ok(history) => history,
+        Err(e) => {
+            debug!("Failed to read the history, starting a new one: {e}");
+            History::default()
+        }
+    }
+}
+
+/// Read the restore file, or the msgpack restore file written by older versions if there is no
+/// restore file yet.
+async fn read_restore_state() -> Result<RestoreState> {
+    let restore_path = server_cli::default_restore_path()?;
+    let content = match tokio::fs::read(&restore_path).await {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            let legacy_path = server_cli::default_legacy_restore_path()?;
+            debug!("No restore file, trying to migrate {legacy_path:?} ...");
+            tokio::fs::read(legacy_path).await?
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    RestoreState::deserialize_from(&content[..])
+}
+
 async fn process_connection(
     task_handle: TaskHandle,
     mut socket: UnixStream,
+    message: ipc::Message,
     request_tx: mpsc::Sender<(TaskHandle, ipc::Message, oneshot::Sender<ipc::Reply>)>,
 ) -> Result<()> {
-    let message = ipc::Message::async_receive(&mut socket).await?;
     let (reply_tx, reply_rx) = oneshot::channel();
     request_tx.send((task_handle, message, reply_tx)).await?;
 
@@ -236,57 +405,53 @@ async fn process_message(
             error!("`Kill` request must be processed in outer scope");
             ipc::Reply::Ok
         }
-        ipc::Message::Image { args } => {
-            let ImageArgs {
-                path,
-                resize,
-                transition,
-                transition_options,
-                ease,
-                fill_rgb,
-            } = args;
-
-            let fill_rgb = (fill_rgb.0 as f64, fill_rgb.1 as f64, fill_rgb.2 as f64);
-
-            if transition != TransitionKind::No {
-                info!("Starting transition: {path:?} ...");
-                info!("Fill color: {fill_rgb:?}");
-                info!("Resize option: {resize:?}");
-                info!("TransitionKind: {transition:?}");
-                info!("EaseKind: {ease:?}");
-
-                let duration = transition_options
-                    .duration
-                    .unwrap_or(server_cli::DEFAULT_TRANSITION_DURATION);
-                let fps = transition_options
-                    .fps
-                    .unwrap_or(server_cli::DEFAULT_TRANSITION_FPS);
-                wallpaper
-                    .start_transition(
-                        qh,
-                        &path,
-                        resize,
-                        fill_rgb,
-                        duration,
-                        fps,
-                        transition,
-                        transition_options,
-                        ease,
-                        task_handle,
-                    )
-                    .await;
-
-                ipc::Reply::Ok
-            } else {
-                info!("Start immediate wallpaper switching: {path:?} ...");
-                info!("Resize option: {resize:?}");
-                let result = wallpaper
-                    .change_image_and_request_frame(qh, &path, resize, fill_rgb)
-                    .await;
-
-                ipc::Reply::from_result(result)
+        ipc::Message::Outputs => ipc::Reply::Outputs(wallpaper.outputs()),
+        ipc::Message::Slideshow { args } => {
+            info!("Starting slideshow: {:?} ...", args.paths);
+            ipc::Reply::from_result(wallpaper.start_slideshow(qh, args).await)
+        }
+        ipc::Message::SlideshowControl { command } => {
+            info!("Slideshow: {command:?}");
+            ipc::Reply::from_result(wallpaper.control_slideshow(qh, command).await)
+        }
+        ipc::Message::Transition { command } => {
+            info!("Transition: {command:?}");
+            ipc::Reply::from_result(wallpaper.control_transition(qh, command))
+        }
+        ipc::Message::Prev => {
+            ipc::Reply::from_result(wallpaper.step_history(qh, -1, task_handle).await)
+        }
+        ipc::Message::Next => {
+            ipc::Reply::from_result(wallpaper.step_history(qh, 1, task_handle).await)
+        }
+        ipc::Message::History => ipc::Reply::History(wallpaper.history().clone()),
+        ipc::Message::Colors { output } => match wallpaper.palette(output.as_deref()) {
+            Ok(palette) => ipc::Reply::Colors(palette),
+            Err(e) => ipc::Reply::Error(e.to_string()),
+        },
+        ipc::Message::Screenshot { output, path } => {
+            info!("Taking screenshots ...");
+            match wallpaper.screenshot(output, path).await {
+                Ok(screenshots) => ipc::Reply::Screenshots(screenshots),
+                Err(e) => ipc::Reply::Error(e.to_string()),
             }
         }
+        ipc::Message::Reload => match Config::load() {
+            Ok(config) => ipc::Reply::Reloaded(wallpaper.reload(qh, config).await),
+            Err(e) => ipc::Reply::Error(e.to_string()),
+        },
+        ipc::Message::Image { args } => {
+            info!("Changing wallpaper: {:?} ...", args.image);
+            info!("Outputs: {:?}", args.outputs);
+            info!("Resize option: {:?}", args.resize);
+            info!("Fill color: {:?}", args.fill_rgb);
+            info!("Padding: {:?}", args.padding);
+            info!("TransitionKind: {:?}", args.transition);
+            info!("EaseKind: {:?}", args.ease);
+
+            let result = wallpaper.change_wallpaper(qh, args, task_handle).await;
+            ipc::Reply::from_result(result)
+        }
     };
 
     reply_tx
@@ -296,6 +461,33 @@ async fn process_message(
     Ok(())
 }
 
+/// SIGUSR1 and SIGHUP reload the config file.
+fn wait_reload_sig() -> Result<mpsc::Receiver<()>> {
+    debug!("Trying to hook reloading signal ...");
+    let (sig_tx, sig_rx) = mpsc::channel(1);
+
+    let mut sigusr1 = tokio::signal::unix::signal(SignalKind::user_defined1())
+        .map_err(|e| anyhow!("Failed to hook SIGUSR1: {e}"))?;
+    let mut sighup = tokio::signal::unix::signal(SignalKind::hangup())
+        .map_err(|e| anyhow!("Failed to hook SIGHUP: {e}"))?;
+
+    tokio::spawn(async move {
+        loop {
+            select! {
+                _ = sigusr1.recv() => {},
+                _ = sighup.recv() => {},
+            };
+
+            // Signals received while a reload is pending are merged into it.
+            if let Err(mpsc::error::TrySendError::Closed(_)) = sig_tx.try_send(()) {
+                break;
+            }
+        }
+    });
+
+    Ok(sig_rx)
+}
+
 async fn wait_shutdown_sig() -> Result<oneshot::Receiver<()>> {
     debug!("Trying to hook stoppeing signal ...");
     let (sig_tx, sig_rx) = oneshot::channel();
@@ -304,8 +496,6 @@ async fn wait_shutdown_sig() -> Result<oneshot::Receiver<()>> {
         .map_err(|e| anyhow!("Failed to hook SIGINT: {e}"))?;
     let mut sigterm = tokio::signal::unix::signal(SignalKind::terminate())
         .map_err(|e| anyhow!("Failed to hook SIGTERM: {e}"))?;
-    let mut sighup = tokio::signal::unix::signal(SignalKind::hangup())
-        .map_err(|e| anyhow!("Failed to hook SIGHUP: {e}"))?;
     let mut sigquit = tokio::signal::unix::signal(SignalKind::quit())
         .map_err(|e| anyhow!("Failed to hook SIGQUIT: {e}"))?;
 
@@ -313,7 +503,6 @@ async fn wait_shutdown_sig() -> Result<oneshot::Receiver<()>> {
         select! {
             _ = sigint.recv() => {},
             _ = sigterm.recv() => {},
-            _ = sighup.recv() => {},
             _ = sigquit.recv() => {},
         };
 
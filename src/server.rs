@@ -97,6 +97,11 @@ impl TaskHandle {
         Self { busy_flag }
     }
 
+    /// A handle which doesn't exclude any other task, e.g. for requests which only read the state.
+    pub fn unshared() -> Self {
+        Self::new(Arc::new(true.into()))
+    }
+
     fn finish(&mut self) {
         match self
             .busy_flag
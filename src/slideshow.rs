@@ -0,0 +1,218 @@
+use anyhow::{Result, anyhow};
+use common::{
+    cli::client::{DEFAULT_SLIDESHOW_INTERVAL, DEFAULT_SLIDESHOW_ORDER, SlideshowOrder},
+    ipc::{ImageArgs, SlideshowArgs},
+    restore::SlideshowPosition,
+};
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+use tokio::time::Instant;
+use tracing::debug;
+
+/// Images displayed one after another on a timer.
+pub struct Slideshow {
+    args: SlideshowArgs,
+    images: Vec<PathBuf>,
+    /// Index of the displayed image in `images`.
+    position: usize,
+    /// When the next image is displayed. `None` if the slideshow is paused.
+    deadline: Option<Instant>,
+    /// Time left until the next image, when the slideshow is paused.
+    remaining: Duration,
+}
+
+impl Slideshow {
+    /// Collect the images of the slideshow, starting from the first one. The directories are
+    /// read, so call this outside the event loop.
+    pub fn new(args: SlideshowArgs) -> Result<Self> {
+        let images = collect_images(&args)?;
+        debug!("{} images are found for the slideshow", images.len());
+
+        let interval = args.interval.unwrap_or(DEFAULT_SLIDESHOW_INTERVAL);
+        Ok(Self {
+            args,
+            images,
+            position: 0,
+            deadline: Some(Instant::now() + interval),
+            remaining: interval,
+        })
+    }
+
+    /// Continue a saved slideshow from the image displayed when it was saved.
+    pub fn resume(position: SlideshowPosition) -> Result<Self> {
+        let mut slideshow = Self::new(position.args)?;
+        if let Some(i) = slideshow
+            .images
+            .iter()
+            .position(|image| *image == position.current)
+        {
+            slideshow.position = i;
+        }
+        if position.paused {
+            slideshow.pause();
+        }
+
+        Ok(slideshow)
+    }
+
+    /// Where the slideshow is, to be saved into the restore file.
+    pub fn position(&self) -> SlideshowPosition {
+        SlideshowPosition {
+            args: self.args.clone(),
+            current: self.current().to_owned(),
+            paused: self.deadline.is_none(),
+        }
+    }
+
+    pub fn current(&self) -> &Path {
+        &self.images[self.position]
+    }
+
+    /// The request displaying the current image.
+    pub fn image_args(&self) -> ImageArgs {
+        self.args.image_args(self.current().to_owned())
+    }
+
+    /// The directories the images are picked from, and whether their subdirectories are too.
+    pub fn directories(&self) -> impl Iterator<Item = (&Path, bool)> {
+        self.args
+            .paths
+            .iter()
+            .filter(|path| path.is_dir())
+            .map(|path| (path.as_path(), self.args.recursive))
+    }
+
+    /// Add a new image found in the directories, in the order of the slideshow. New images of a
+    /// random slideshow are displayed in this round. Return `false` if it's already added.
+    pub fn add(&mut self, image: PathBuf) -> bool {
+        if self.images.contains(&image) {
+            return false;
+        }
+
+        let i = match self.order() {
+            SlideshowOrder::Random => fastrand::usize(self.position + 1..=self.images.len()),
+            SlideshowOrder::Name => self.images.partition_point(|other| *other < image),
+            // New images are the newest.
+            SlideshowOrder::Mtime => self.images.len(),
+        };
+        self.images.insert(i, image);
+        if i <= self.position {
+            self.position += 1;
+        }
+
+        true
+    }
+
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    fn interval(&self) -> Duration {
+        self.args.interval.unwrap_or(DEFAULT_SLIDESHOW_INTERVAL)
+    }
+
+    /// Move to the next image, and display it for a whole interval.
+    pub fn next(&mut self) {
+        self.position += 1;
+        if self.position == self.images.len() {
+            self.position = 0;
+            if self.order() == SlideshowOrder::Random {
+                self.reshuffle();
+            }
+        }
+        self.restart_timer();
+    }
+
+    /// Move to the previous image, and display it for a whole interval.
+    pub fn prev(&mut self) {
+        self.position = self
+            .position
+            .checked_sub(1)
+            .unwrap_or(self.images.len() - 1);
+        self.restart_timer();
+    }
+
+    pub fn pause(&mut self) {
+        if let Some(deadline) = self.deadline.take() {
+            self.remaining = deadline.saturating_duration_since(Instant::now());
+        }
+    }
+
+    /// Continue the timer from where it was paused.
+    pub fn resume_timer(&mut self) {
+        if self.deadline.is_none() {
+            self.deadline = Some(Instant::now() + self.remaining);
+        }
+    }
+
+    fn restart_timer(&mut self) {
+        let interval = self.interval();
+        match &mut self.deadline {
+            Some(deadline) => *deadline = Instant::now() + interval,
+            None => self.remaining = interval,
+        }
+    }
+
+    fn order(&self) -> SlideshowOrder {
+        self.args.order.unwrap_or(DEFAULT_SLIDESHOW_ORDER)
+    }
+
+    /// Shuffle the images for another round, without displaying the last image twice in a row.
+    fn reshuffle(&mut self) {
+        let Some(last) = self.images.last().cloned() else {
+            return;
+        };
+        fastrand::shuffle(&mut self.images);
+        if self.images.len() > 1 && self.images[0] == last {
+            let i = fastrand::usize(1..self.images.len());
+            self.images.swap(0, i);
+        }
+    }
+}
+
+/// The images in the directories of `args`, and the images given directly, in the order of
+/// `args`.
+fn collect_images(args: &SlideshowArgs) -> Result<Vec<PathBuf>> {
+    let mut images = vec![];
+    for path in &args.paths {
+        if path.is_dir() {
+            read_dir(path, args.recursive, &mut images)
+                .map_err(|e| anyhow!("Failed to read directory {path:?}: {e}"))?;
+        } else {
+            images.push(path.clone());
+        }
+    }
+
+    if images.is_empty() {
+        return Err(anyhow!("No image is found in {:?}", args.paths));
+    }
+
+    match args.order.unwrap_or(DEFAULT_SLIDESHOW_ORDER) {
+        SlideshowOrder::Random => fastrand::shuffle(&mut images),
+        SlideshowOrder::Name => images.sort(),
+        SlideshowOrder::Mtime => images.sort_by_cached_key(|image| {
+            std::fs::metadata(image)
+                .and_then(|metadata| metadata.modified())
+                .unwrap_or(SystemTime::UNIX_EPOCH)
+        }),
+    }
+
+    Ok(images)
+}
+
+fn read_dir(dir: &Path, recursive: bool, images: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            if recursive {
+                read_dir(&path, recursive, images)?;
+            }
+        } else if image::ImageFormat::from_path(&path).is_ok() {
+            images.push(path);
+        }
+    }
+
+    Ok(())
+}
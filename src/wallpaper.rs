@@ -2,24 +2,36 @@ mod bind_group;
 mod config;
 mod misc;
 mod off_screen;
+mod padding;
 mod render_pipeline;
 mod sampler;
+mod scale;
 mod shaders;
+mod surface;
 mod texture;
 mod transition_state;
 mod vertex;
 
 use anyhow::{Result, anyhow};
 use common::{
+    cli::client::{SlideshowCommand, TransitionCommand},
     cli::{
-        client::{EaseKind, TransitionKind, TransitionOptions},
+        client::{EaseKind, Fill, Gradient, Padding, TransitionKind, TransitionOptions},
         server as server_cli,
     },
-    restore::Restore,
+    config::{Config, Defaults, Filter},
+    history::History,
+    ipc::{self, ImageArgs, ImageSource, SlideshowArgs},
+    palette::{self, Palette},
+    restore::{Restore, RestoreState, SlideshowPosition},
+    schedule::{Schedule, ScheduleConfig, ScheduledWallpaper},
     utils,
 };
-use config::Configurable;
+use image::RgbaImage;
+use notify::RecursiveMode;
 use off_screen::OffScreen;
+use padding::PaddingImages;
+use scale::ScaleState;
 use smithay_client_toolkit::{
     compositor::{CompositorHandler, CompositorState},
     delegate_compositor, delegate_layer, delegate_output, delegate_registry, delegate_shm,
@@ -28,24 +40,32 @@ use smithay_client_toolkit::{
     registry_handlers,
     shell::{
         WaylandSurface,
-        wlr_layer::{
-            Anchor, Layer, LayerShell, LayerShellHandler, LayerSurface, LayerSurfaceConfigure,
-        },
+        wlr_layer::{LayerShell, LayerShellHandler, LayerSurface},
     },
     shm::{Shm, ShmHandler},
 };
-use std::path::{Path, PathBuf};
-use tracing::{debug, error, warn};
-use wayland_client::{Connection, QueueHandle, globals::GlobalList};
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+use surface::{LayerOptions, OutputSurface};
+use tokio::{io::AsyncWriteExt, task::JoinHandle, time::Instant};
+use tracing::{debug, error, info, warn};
+use wayland_client::{
+    Connection, Proxy, QueueHandle,
+    backend::ObjectId,
+    globals::GlobalList,
+    protocol::{wl_output::Transform, wl_surface::WlSurface},
+};
 use wgpu::{self, util::DeviceExt};
 
 use crate::{
-    ease,
+    hooks::{self, HookEvent},
     server::TaskHandle,
-    wallpaper::{
-        transition_state::{TransitionRenderError, TransitionState},
-        vertex::NUM_INDEX,
-    },
+    slideshow::Slideshow,
+    watcher::{self, FileWatcher},
 };
 
 delegate_registry!(Wallpaper);
@@ -54,20 +74,26 @@ delegate_compositor!(Wallpaper);
 delegate_layer!(Wallpaper);
 delegate_shm!(Wallpaper);
 
-// TODO: Support sampler filter configuration in cli.
+// Filters of the samplers of off-screen buffers and transitions. The filters of the image sampler
+// are configured by the user.
 const MAG_FILTER: wgpu::FilterMode = wgpu::FilterMode::Linear;
 const MIN_FILTER: wgpu::FilterMode = wgpu::FilterMode::Nearest;
 const MIPMAP_FILTER: wgpu::FilterMode = wgpu::FilterMode::Nearest;
 
+// The schedule is checked at least this often, because timers of the event loop don't count the
+// time the system is suspended.
+const SCHEDULE_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
 #[derive(Default)]
 pub struct WallpaperBuilder {
-    load_wallpaper: Option<PathBuf>,
-    fill_color: Option<(f64, f64, f64)>,
-    resize_option: Option<server_cli::ResizeOption>,
-
-    mag_filter: Option<wgpu::FilterMode>,
-    min_filter: Option<wgpu::FilterMode>,
-    mipmap_filter: Option<wgpu::FilterMode>,
+    content: Option<ContentArgs>,
+    output_contents: HashMap<String, ContentArgs>,
+    slideshow: Option<SlideshowPosition>,
+    history: History,
+    schedule: bool,
+    file_watcher: Option<FileWatcher>,
+    config: Option<Config>,
+    layer_options: Option<LayerOptions>,
 }
 
 impl WallpaperBuilder {
@@ -75,57 +101,60 @@ impl WallpaperBuilder {
         Self::default()
     }
 
-    pub fn with_img_path(mut self, path: impl Into<PathBuf>) -> Self {
-        self.load_wallpaper = Some(path.into());
+    pub fn with_content(mut self, content: ContentArgs) -> Self {
+        self.content = Some(content);
         self
     }
 
-    pub fn with_fill_color(mut self, rgb: (f64, f64, f64)) -> Self {
-        self.fill_color = Some(rgb);
+    /// Wallpapers of specific outputs, by output name.
+    pub fn with_output_contents(mut self, output_contents: HashMap<String, ContentArgs>) -> Self {
+        self.output_contents = output_contents;
         self
     }
 
-    pub fn with_resize_option(self, resize_option: server_cli::ResizeOption) -> Self {
-        Self {
-            resize_option: Some(resize_option),
-            ..self
-        }
+    /// A saved slideshow, continued from where it was.
+    pub fn with_slideshow(mut self, slideshow: Option<SlideshowPosition>) -> Self {
+        self.slideshow = slideshow;
+        self
     }
 
-    #[allow(unused)]
-    pub fn with_mag_filter_mode(mut self, filter: wgpu::FilterMode) -> Self {
-        self.mag_filter = Some(filter);
+    /// Wallpapers applied before, which can be gone back to.
+    pub fn with_history(mut self, history: History) -> Self {
+        self.history = history;
         self
     }
 
-    #[allow(unused)]
-    pub fn with_min_filter_mode(mut self, filter: wgpu::FilterMode) -> Self {
-        self.min_filter = Some(filter);
+    /// Whether the configured schedule decides the wallpaper from startup. A continued slideshow
+    /// takes precedence over it.
+    pub fn with_schedule(mut self, schedule: bool) -> Self {
+        self.schedule = schedule;
         self
     }
 
-    #[allow(unused)]
-    pub fn with_mipmap_filter_mode(mut self, filter: wgpu::FilterMode) -> Self {
-        self.mipmap_filter = Some(filter);
+    /// Watches the files as configured in `[watch]`.
+    pub fn with_file_watcher(mut self, file_watcher: FileWatcher) -> Self {
+        self.file_watcher = Some(file_watcher);
         self
     }
 
-    #[tracing::instrument(skip(self, conn, globals, qh, namespace))]
+    pub fn with_config(mut self, config: Config) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    pub fn with_surface_options(mut self, options: &server_cli::SurfaceOptions) -> Result<Self> {
+        self.layer_options = Some(LayerOptions::from_cli(options)?);
+        Ok(self)
+    }
+
+    #[tracing::instrument(skip(self, globals, qh, namespace))]
     pub async fn build(
         self,
-        conn: &Connection,
         globals: &GlobalList,
         qh: &QueueHandle<Wallpaper>,
         namespace: Option<impl Into<String>>,
     ) -> Result<Wallpaper> {
-        let load_wallpaper = self
-            .load_wallpaper
-            .ok_or(anyhow!("No wallpaper provided"))?;
-
-        let fill_color = self.fill_color.ok_or(anyhow!("No fill color provided"))?;
-
-        debug!("Trying to create a wgpu instance ...");
-        let instance = wgpu::Instance::new(&misc::instance_desc());
+        let config = self.config.unwrap_or_default();
 
         debug!("Trying to prepare wayland event handlers ...");
         let registry_state = RegistryState::new(globals);
@@ -133,37 +162,279 @@ impl WallpaperBuilder {
         let compositor_state = CompositorState::bind(globals, qh)?;
         let shm_state = Shm::bind(globals, qh)?;
         let layer_shell_state = LayerShell::bind(globals, qh)?;
+        let scale_state = ScaleState::bind(globals, qh);
 
-        debug!("Trying to create a wayland layer surface");
-        let orig_surface = compositor_state.create_surface(qh);
-        let layer_surface = layer_shell_state.create_layer_surface(
-            qh,
-            orig_surface,
-            Layer::Background,
-            namespace,
-            None,
-        );
+        let gpu = Gpu::new().await?;
 
-        debug!("Configuring the layer surface ...");
-        // Ask the compositor to decide the size.
-        layer_surface.set_size(0, 0);
-        layer_surface.set_anchor(Anchor::all());
-        // Ask the compositors not to move this surface to accommodate for other surfaces, and to
-        // extend this surface all the way to the edges it anchored.
-        layer_surface.set_exclusive_zone(-1);
-        layer_surface.set_keyboard_interactivity(
-            smithay_client_toolkit::shell::wlr_layer::KeyboardInteractivity::None,
-        );
-        // Do not forget to commit the surface, or we will never receive the first `configure`
-        // event.
-        layer_surface.commit();
+        // The surfaces are mapped with the fill color, and the wallpaper is decoded later in
+        // `fade_in`, so that the screen isn't left empty while decoding.
+        let request = Wallpaper::fill_color(&config);
+        let img = DecodedImage::placeholder();
+
+        let schedule = match &config.schedule {
+            Some(schedule) if self.schedule && self.slideshow.is_none() => {
+                load_schedule(schedule.clone()).await
+            }
+            _ => None,
+        };
+
+        // Surfaces are created once the outputs are advertised by the compositor.
+        debug!("Wallpaper built!");
+        Ok(Wallpaper {
+            registry_state,
+            output_state,
+            compositor_state,
+            shm_state,
+            layer_shell_state,
+            scale_state,
+
+            gpu,
+            config,
+            layer_options: self.layer_options.unwrap_or_default(),
+            namespace: namespace.map(Into::into),
+            request,
+            img,
+            output_requests: HashMap::new(),
+            surfaces: vec![],
+            slideshow: None,
+            history: self.history,
+            schedule,
+            schedule_deadline: None,
+            scheduled: None,
+            file_watcher: self.file_watcher,
+            changed_files: HashSet::new(),
+            watch_deadline: None,
+            pending_outputs: vec![],
+            transition_speed: 1.0,
+            initial_request: self.content,
+            initial_output_requests: self.output_contents,
+            initial_slideshow: self.slideshow,
+        })
+    }
+}
+
+/// What to display, and how to transition to it, as requested by the user. Options which are not
+/// given fall back to the defaults configured for each output.
+#[derive(Clone, Debug)]
+pub struct ContentArgs {
+    pub image: ImageSource,
+    pub resize: Option<server_cli::ResizeOption>,
+    pub tile: server_cli::TileOptions,
+    pub placement: server_cli::Placement,
+    pub source_transform: server_cli::SourceTransform,
+    pub fill_rgb: Option<(u8, u8, u8)>,
+    pub padding: Padding,
+    /// Magnification, minification and mipmap filters of the image sampler.
+    pub filters: Option<(Filter, Filter, Filter)>,
+    pub transition: Option<TransitionKind>,
+    pub transition_options: TransitionOptions,
+    pub ease: Option<EaseKind>,
+    /// Extracted from the image once it's decoded.
+    pub palette: Option<Palette>,
+}
+
+impl ContentArgs {
+    /// Display `image` with the default options.
+    pub fn new(image: ImageSource) -> Self {
+        Self {
+            image,
+            resize: None,
+            tile: server_cli::TileOptions::default(),
+            placement: server_cli::Placement::default(),
+            source_transform: server_cli::SourceTransform::default(),
+            fill_rgb: None,
+            padding: Padding::default(),
+            filters: None,
+            transition: None,
+            transition_options: TransitionOptions::default(),
+            ease: None,
+            palette: None,
+        }
+    }
+
+    /// Extract the palette of the wallpaper, whose image is decoded as `img`.
+    fn compute_palette(&mut self, img: &RgbaImage) {
+        self.palette = Some(match &self.image {
+            ImageSource::Color(rgb) => Palette::from_colors(&[*rgb]),
+            ImageSource::Gradient(gradient) => Palette::from_colors(
+                &gradient
+                    .stops
+                    .iter()
+                    .map(|stop| stop.rgb)
+                    .collect::<Vec<_>>(),
+            ),
+            ImageSource::Path(_) | ImageSource::Data(_) => {
+                Palette::from_rgba(img.as_raw(), img.width(), img.height())
+            }
+        });
+    }
+
+    fn fill_rgb(&self, defaults: &Defaults) -> (u8, u8, u8) {
+        defaults.fill_rgb(self.fill_rgb, self.palette.as_ref())
+    }
+
+    fn resolve(&self, defaults: &Defaults) -> Content {
+        Content {
+            image: self.image.clone(),
+            resize_option: defaults.resize(self.resize),
+            tile: self.tile,
+            placement: self.placement,
+            source_transform: self.source_transform,
+            fill_rgb: utils::rgb_u8_to_f64(self.fill_rgb(defaults)),
+            fill: defaults.fill(&self.padding),
+            filters: filter_modes(self.filters.unwrap_or(defaults.filters())),
+        }
+    }
+
+    /// The request displaying this wallpaper on all outputs.
+    fn image_args(&self) -> ImageArgs {
+        ImageArgs {
+            image: self.image.clone(),
+            outputs: vec![],
+            resize: self.resize,
+            tile: self.tile,
+            placement: self.placement,
+            source_transform: self.source_transform,
+            fill_rgb: self.fill_rgb,
+            padding: self.padding,
+            transition: self.transition,
+            transition_options: self.transition_options,
+            ease: self.ease,
+        }
+    }
+
+    /// The wallpaper as displayed with `defaults`, to be saved into the restore file.
+    fn to_restore(&self, defaults: &Defaults, source: ImageSource) -> Restore {
+        Restore {
+            source,
+            resize_option: defaults.resize(self.resize),
+            fill_rgb: self.fill_rgb(defaults),
+            fill: defaults.fill(&self.padding),
+            tile: self.tile,
+            placement: self.placement,
+            source_transform: self.source_transform,
+            filters: Some(self.filters.unwrap_or(defaults.filters())),
+            transition: self.transition,
+            transition_options: self.transition_options,
+            ease: self.ease,
+        }
+    }
+}
+
+impl From<Restore> for ContentArgs {
+    fn from(restore: Restore) -> Self {
+        Self {
+            image: restore.source,
+            resize: Some(restore.resize_option),
+            tile: restore.tile,
+            placement: restore.placement,
+            source_transform: restore.source_transform,
+            fill_rgb: Some(restore.fill_rgb),
+            padding: Padding::from(restore.fill),
+            filters: restore.filters,
+            transition: restore.transition,
+            transition_options: restore.transition_options,
+            ease: restore.ease,
+            palette: None,
+        }
+    }
+}
+
+impl From<&ImageArgs> for ContentArgs {
+    fn from(args: &ImageArgs) -> Self {
+        Self {
+            image: args.image.clone(),
+            resize: args.resize,
+            tile: args.tile,
+            placement: args.placement,
+            source_transform: args.source_transform,
+            fill_rgb: args.fill_rgb,
+            padding: args.padding,
+            filters: None,
+            transition: args.transition,
+            transition_options: args.transition_options,
+            ease: args.ease,
+            palette: None,
+        }
+    }
+}
+
+/// What is displayed on a surface: the image, and how it is placed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Content {
+    pub image: ImageSource,
+    pub resize_option: server_cli::ResizeOption,
+    pub tile: server_cli::TileOptions,
+    pub placement: server_cli::Placement,
+    pub source_transform: server_cli::SourceTransform,
+    pub fill_rgb: (f64, f64, f64),
+    pub fill: Fill,
+    pub filters: Filters,
+}
+
+impl Content {
+    fn procedural_gradient(&self) -> Option<Gradient> {
+        match &self.image {
+            ImageSource::Color(rgb) => Some(Gradient::solid(*rgb)),
+            ImageSource::Gradient(gradient) => Some(gradient.clone()),
+            ImageSource::Path(_) | ImageSource::Data(_) => None,
+        }
+    }
+}
+
+/// A decoded wallpaper, with the copies of it drawn in its paddings. It's cheap to clone.
+#[derive(Clone)]
+pub struct DecodedImage {
+    pub rgba: Arc<RgbaImage>,
+    pub paddings: Arc<PaddingImages>,
+}
 
-        debug!("Trying to create a wgpu surface");
-        let wgpu_surface = misc::layer_surface_to_wgpu_surface(conn, &layer_surface, &instance)?;
+impl DecodedImage {
+    /// Stands in for wallpapers which are not backed by any image. It's never drawn.
+    fn placeholder() -> Self {
+        Self {
+            rgba: Arc::new(RgbaImage::new(1, 1)),
+            paddings: Arc::default(),
+        }
+    }
+}
+
+/// Magnification, minification and mipmap filters of the image sampler.
+type Filters = (wgpu::FilterMode, wgpu::FilterMode, wgpu::FilterMode);
+
+fn filter_modes((mag_filter, min_filter, mipmap_filter): (Filter, Filter, Filter)) -> Filters {
+    let filter_mode = |filter| match filter {
+        Filter::Nearest => wgpu::FilterMode::Nearest,
+        Filter::Linear => wgpu::FilterMode::Linear,
+    };
+    (
+        filter_mode(mag_filter),
+        filter_mode(min_filter),
+        filter_mode(mipmap_filter),
+    )
+}
+
+/// Rendering resources shared by the surfaces of all outputs.
+struct Gpu {
+    instance: wgpu::Instance,
+    adapter: wgpu::Adapter,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    /// Draws images into off-screen buffers, so it doesn't depend on the format of any surface.
+    render_pipeline: wgpu::RenderPipeline,
+    index_buffer: wgpu::Buffer,
+}
 
+impl Gpu {
+    async fn new() -> Result<Self> {
+        debug!("Trying to create a wgpu instance ...");
+        let instance = wgpu::Instance::new(&misc::instance_desc());
+
+        // No surface exists before the outputs are advertised, so the adapter can't be chosen by
+        // its compatibility with one.
         debug!("Trying to request a wgpu adapter ...");
         let adapter = instance
-            .request_adapter(&misc::adapter_options(&wgpu_surface))
+            .request_adapter(&misc::adapter_options(None))
             .await?;
 
         debug!("Trying to get a wgpu device and queue ...");
@@ -171,27 +442,7 @@ impl WallpaperBuilder {
             .request_device(&misc::device_desc(Some("pwwwd")))
             .await?;
 
-        debug!("Trying to create a surface configuration ...");
-        let surface_caps = wgpu_surface.get_capabilities(&adapter);
-        let surface_format = surface_caps
-            .formats
-            .iter()
-            .find(|f| f.is_srgb())
-            .copied()
-            .unwrap_or(surface_caps.formats[0]);
-        let config = config::create(&surface_format, &surface_caps);
-
-        debug!("Trying to create the vertex buffer and index buffer ...");
-        let resize_option = self
-            .resize_option
-            .ok_or(anyhow!("No resize option provided"))?;
-        // HACK: As we don't know the surface size for now, use `stretch` to create the vertex buffer.
-        let vertex_buffer = vertex::STRETCH_VERTICES;
-        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Vertex buffer"),
-            contents: bytemuck::cast_slice(vertex_buffer),
-            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
-        });
+        debug!("Trying to create the index buffer ...");
         let index_buffer = vertex::CCW_INDICES;
         let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Index buffer"),
@@ -199,67 +450,13 @@ impl WallpaperBuilder {
             usage: wgpu::BufferUsages::INDEX,
         });
 
-        let img = image::open(&load_wallpaper)?;
-        let img = img.to_rgba8();
-        let texture_width = img.width();
-        let texture_height = img.height();
-        let image_texture = {
-            debug!("Trying to create and write to the texture ...");
-            let size = texture::texture_size(texture_width, texture_height);
-            let desc = texture::image_srgb_unorm_desc(None, size, 1);
-            let texture = device.create_texture(&desc);
-            queue.write_texture(
-                wgpu::TexelCopyTextureInfo {
-                    texture: &texture,
-                    mip_level: 0,
-                    origin: wgpu::Origin3d::ZERO,
-                    aspect: wgpu::TextureAspect::All,
-                },
-                &img,
-                wgpu::TexelCopyBufferLayout {
-                    offset: 0,
-                    bytes_per_row: Some(4 * texture_width),
-                    rows_per_image: Some(texture_height),
-                },
-                size,
-            );
-
-            texture
-        };
-
-        // After loading the image, try to save the path into state file.
-        Wallpaper::save_image_path_to_restore_file(&load_wallpaper, resize_option, fill_color)
-            .await;
-
-        debug!("Trying to create a sampler ...");
-        let sampler = device.create_sampler(&sampler::desc(
-            None,
-            (
-                wgpu::AddressMode::ClampToEdge,
-                wgpu::AddressMode::ClampToEdge,
-            ),
-            self.mag_filter.unwrap_or(MAG_FILTER),
-            self.min_filter.unwrap_or(MIN_FILTER),
-            self.mipmap_filter.unwrap_or(MIPMAP_FILTER),
-        ));
-
-        debug!("Trying to bind the texture and the sampler together ...");
-        let image_texture_view = image_texture.create_view(&texture::image_view_desc(None));
-        let layout =
-            device.create_bind_group_layout(&bind_group::texture_and_sampler::layout_desc(None));
-        let bind_group = bind_group::texture_and_sampler::bind_group(
-            &device,
-            None,
-            &layout,
-            &image_texture_view,
-            &sampler,
-        );
-
         debug!("Trying to build the wallpaper shader ...");
         let shader =
             device.create_shader_module(wgpu::include_wgsl!("wallpaper/shaders/wallpaper.wgsl"));
 
         debug!("Trying to build the wallpaper render pipeline ...");
+        let layout =
+            device.create_bind_group_layout(&bind_group::texture_and_sampler::layout_desc(None));
         let render_pipeline = render_pipeline::create_pipeline(
             &device,
             None,
@@ -269,487 +466,1182 @@ impl WallpaperBuilder {
             Some("vs_main"),
             shaders::wallpaper::BUFFERS,
             Some("fs_main"),
-            // &shaders::wallpaper::targets(config.format),
             &shaders::wallpaper::target(OffScreen::format()),
         );
 
-        debug!("Creating off-screen buffer ...");
-        // HACK: As we don't know the surface size for now, use `1920x1080` to create the
-        // off-screen buffer.
-        let off_screen_buffer = off_screen::OffScreen::create(&device, (1920, 1080), config.format);
-
-        debug!("Wallpaper built!");
-        Ok(Wallpaper {
-            layer_surface,
-            exited: false,
-            first_configured: false,
-            damaged: true,
-
-            off_screen_buffer,
-
-            registry_state,
-            output_state,
-            compositor_state,
-            shm_state,
-            layer_shell_state,
-
+        Ok(Self {
+            instance,
+            adapter,
             device,
             queue,
-            wgpu_surface,
-            config,
-
-            image_texture,
-            texture_width,
-            texture_height,
-            vertex_buffer,
-            resize_option,
-            index_buffer,
-            fill_color,
-            sampler,
-            bind_group,
             render_pipeline,
-
-            transition: None,
+            index_buffer,
         })
     }
 }
 
 pub struct Wallpaper {
-    layer_surface: LayerSurface,
-
-    // States
-    /// Whether the daemon exited. If this flag is true, we should stop the event loop and exit.
-    pub exited: bool,
-    /// Whether the daemon finished the first configuration. If this flag is false, we cannot
-    /// render to the surface and commit. Once `LayerShellHandler::configure` is called, this flag
-    /// will be set to true.
-    first_configured: bool,
-    /// Whether we have something new to be drawed. `draw` method will render to the surface and
-    /// commit if both `first_configured` and `damaged` are true. This flag will be set to true
-    /// when:
-    ///
-    /// 1. A `new_size` is received by the daemon from the `LayerShellHandler::configure` method.
-    /// 2. TODO: A new image path is received by the daemon from the client.
-    /// 3. TODO: The daemon is doing transition work between two images.
-    damaged: bool,
-
-    /// Off-screen buffer
-    off_screen_buffer: OffScreen,
-
     // Wayland event handlers,
     registry_state: RegistryState,
     output_state: OutputState,
-    #[allow(unused)]
     compositor_state: CompositorState,
     shm_state: Shm,
-    #[allow(unused)]
     layer_shell_state: LayerShell,
-
-    // Image
-    // image_rgba: RgbaImage,
-    //
-    // Because we will write the image into wgpu's texture later, we don't need to store the image
-    // in struct's field.
-
-    // Wgpu stuffs
-    device: wgpu::Device,
-    queue: wgpu::Queue,
-    wgpu_surface: wgpu::Surface<'static>,
-    config: wgpu::SurfaceConfiguration,
-
-    image_texture: wgpu::Texture,
-    texture_width: u32,
-    texture_height: u32,
-    vertex_buffer: wgpu::Buffer,
-    resize_option: server_cli::ResizeOption,
-    index_buffer: wgpu::Buffer,
-    fill_color: (f64, f64, f64),
-    sampler: wgpu::Sampler,
-    bind_group: wgpu::BindGroup,
-    render_pipeline: wgpu::RenderPipeline,
-
-    // Transition state manager
-    transition: Option<TransitionState>,
+    scale_state: ScaleState,
+
+    gpu: Gpu,
+    config: Config,
+    layer_options: LayerOptions,
+    /// Namespace of the layer surfaces.
+    namespace: Option<String>,
+    /// Displayed on outputs which don't have a wallpaper of their own.
+    request: ContentArgs,
+    /// `request` decoded, so it isn't decoded again whenever an output is plugged in.
+    img: DecodedImage,
+    /// Wallpapers set for specific outputs, by output name. They are kept after the output is
+    /// unplugged, so it gets its wallpaper back when it is plugged in again.
+    output_requests: HashMap<String, ContentArgs>,
+    /// One surface for each output.
+    surfaces: Vec<OutputSurface>,
+    slideshow: Option<Slideshow>,
+    history: History,
+    schedule: Option<Schedule>,
+    /// When the schedule is checked next.
+    schedule_deadline: Option<Instant>,
+    /// The scheduled wallpaper last displayed, which isn't displayed again until the schedule
+    /// changes it.
+    scheduled: Option<ImageSource>,
+    file_watcher: Option<FileWatcher>,
+    /// Watched files changed on disk, which are loaded once they stay unchanged until
+    /// `watch_deadline`.
+    changed_files: HashSet<PathBuf>,
+    watch_deadline: Option<Instant>,
+    /// Outputs plugged in, whose remembered wallpapers are not decoded yet.
+    pending_outputs: Vec<String>,
+    /// Multiplies the speed of all transitions, e.g. `0.1` for slow motion.
+    transition_speed: f64,
+    /// Wallpapers given on the command line or restored, which are faded in by `fade_in`.
+    initial_request: Option<ContentArgs>,
+    initial_output_requests: HashMap<String, ContentArgs>,
+    initial_slideshow: Option<SlideshowPosition>,
 }
 
 impl Wallpaper {
-    #[tracing::instrument(skip(self, _conn, qh))]
-    pub fn draw(&mut self, _conn: &Connection, qh: &QueueHandle<Self>) {
-        if !self.first_configured {
-            warn!("The surface hasn't be configured yet. Stop drawing ...");
-            return;
-        }
-
-        if !self.damaged {
-            debug!("The surface has nothing new to draw. Stop drawing ...");
-            return;
+    /// Change the wallpaper of the requested outputs, or of all outputs if none is given. The
+    /// transition and the options not given are decided by the defaults of each output. If the
+    /// image cannot be loaded, nothing is changed. Changing the wallpaper of all outputs stops the
+    /// slideshow. The new wallpaper is recorded in the history.
+    pub async fn change_wallpaper(
+        &mut self,
+        qh: &QueueHandle<Self>,
+        args: ImageArgs,
+        task_handle: TaskHandle,
+    ) -> Result<()> {
+        // Record the wallpaper displayed before the first change, so that it can be gone back to.
+        if self.history.entries.is_empty() {
+            let size = self.config.history_size();
+            self.history.push(self.request.image_args(), size);
         }
-        self.damaged = false;
 
-        let output = match self.wgpu_surface.get_current_texture() {
-            Ok(output) => output,
-            Err(e) => {
-                error!("Cannot get the current texture of the surface! : {e}");
-                return;
-            }
-        };
-        let view = output
-            .texture
-            .create_view(&texture::surface_view_desc(Some("Surface texture view")));
-
-        if let Some(mut transition_state) = self.transition.take() {
-            // If we have some animating transition, hijack the normal rendering progress.
-            let mut encoder = self
-                .device
-                .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
-
-            let finished = match self.draw_transition(&mut transition_state, &mut encoder, &view) {
-                Ok(_) => false,
-                Err(e) => match e {
-                    TransitionRenderError::SameFrame => false,
-                    TransitionRenderError::Finished => true,
-                },
-            };
+        self.switch_wallpaper(qh, args.clone(), task_handle).await?;
 
-            if !finished {
-                // Continue the transition in the next frame.
-                self.transition = Some(transition_state);
-            } else {
-                // Or the next frame will just show the final image.
-                self.off_screen_buffer
-                    .render_pass(&mut encoder, &view, self.fill_color);
-            }
+        self.history.push(args, self.config.history_size());
+        self.save_history().await;
+        Ok(())
+    }
 
-            self.damaged = true;
-            let surface = self.layer_surface.wl_surface().clone();
-            self.layer_surface.wl_surface().frame(qh, surface);
+    /// Go `offset` wallpapers forward in the history, or backward if `offset` is negative. The
+    /// transition is decided by the defaults, instead of the one recorded in the history.
+    pub async fn step_history(
+        &mut self,
+        qh: &QueueHandle<Self>,
+        offset: isize,
+        task_handle: TaskHandle,
+    ) -> Result<()> {
+        let args = self.history.peek(offset).ok_or(anyhow!(
+            "No {} wallpaper in the history",
+            if offset < 0 { "previous" } else { "next" }
+        ))?;
+        let args = ImageArgs {
+            transition: None,
+            transition_options: TransitionOptions::default(),
+            ease: None,
+            ..args.clone()
+        };
 
-            debug!("Damaging the whole surface ...");
-            let width = self.config.width as i32;
-            let height = self.config.height as i32;
-            self.layer_surface.wl_surface().damage(0, 0, width, height);
-            self.queue.submit(std::iter::once(encoder.finish()));
+        self.switch_wallpaper(qh, args, task_handle).await?;
 
-            debug!("Submitted a transition frame!");
-            output.present();
-            return;
-        }
+        self.history.step(offset);
+        self.save_history().await;
+        Ok(())
+    }
 
-        debug!("Normal rendering. Damaging the whole surface ...");
-        let width = self.config.width as i32;
-        let height = self.config.height as i32;
-        self.layer_surface.wl_surface().damage(0, 0, width, height);
+    pub fn history(&self) -> &History {
+        &self.history
+    }
 
-        let mut encoder = self
-            .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
-        {
-            self.off_screen_buffer.update_pass(
-                &self.device,
-                &mut encoder,
-                (self.config.width, self.config.height),
-                self.fill_color,
-                &self.render_pipeline,
-                &[&self.bind_group],
-                &self.vertex_buffer,
-                &self.index_buffer,
-                vertex::NUM_INDEX,
-            );
-            self.off_screen_buffer
-                .render_pass(&mut encoder, &view, self.fill_color);
-        }
-
-        self.queue.submit(std::iter::once(encoder.finish()));
-        output.present();
-    }
-
-    #[tracing::instrument(skip(self, conn, qh))]
-    pub fn config(
+    async fn switch_wallpaper(
         &mut self,
-        configuration: LayerSurfaceConfigure,
-        conn: &Connection,
         qh: &QueueHandle<Self>,
-    ) {
-        self.config.with_new_size(configuration.new_size);
-        self.wgpu_surface.configure(&self.device, &self.config);
-        self.first_configured = true;
-
-        // HACK: This the only way I know to get the surface size. Write to vertex buffer here.
-        debug!("Writing into vertex buffer ...");
-        let vertex_buffer = vertex::create_vertex_buffer_with_resize_option(
-            (self.config.width, self.config.height),
-            (self.texture_width, self.texture_height),
-            self.resize_option,
-        );
-        self.queue
-            .write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&vertex_buffer));
+        args: ImageArgs,
+        task_handle: TaskHandle,
+    ) -> Result<()> {
+        // Nothing is stopped if the new wallpaper cannot be loaded.
+        let (request, img) = self.load_wallpaper(&args).await?;
+        if args.outputs.is_empty() && self.slideshow.take().is_some() {
+            info!("The slideshow is stopped by the new wallpaper");
+        }
+        if args.outputs.is_empty() && self.schedule.take().is_some() {
+            info!("The schedule is stopped by the new wallpaper");
+        }
 
-        self.draw(conn, qh);
+        self.apply_wallpaper(qh, args, request, img, Some(task_handle))
+            .await
     }
 
-    #[tracing::instrument(skip(self, qh))]
-    pub async fn change_image_and_request_frame(
+    #[tracing::instrument(skip(self, qh, task_handle))]
+    async fn set_wallpaper(
         &mut self,
         qh: &QueueHandle<Self>,
-        image_path: &Path,
-        resize_option: server_cli::ResizeOption,
-        fill_rgb: (f64, f64, f64),
+        args: ImageArgs,
+        task_handle: Option<TaskHandle>,
     ) -> Result<()> {
+        let (request, img) = self.load_wallpaper(&args).await?;
+        self.apply_wallpaper(qh, args, request, img, task_handle)
+            .await
+    }
+
+    /// Load the wallpaper requested by `args`, without changing anything yet.
+    async fn load_wallpaper(&self, args: &ImageArgs) -> Result<(ContentArgs, DecodedImage)> {
+        let mut request = ContentArgs::from(args);
+        self.targets(&args.outputs)?;
+
         // Load the new image.
-        debug!("Trying to load the new image: {image_path:?}");
-        let img = match image::open(image_path) {
+        debug!("Trying to load the new image: {:?}", request.image);
+        let img = match self.decode_wallpaper(&request).await {
             Ok(img) => img,
             Err(e) => {
-                let report = format!("Failed to load the new image in `{image_path:?}`: {e}");
+                let report = format!(
+                    "Failed to load the new image from `{:?}`: {e}",
+                    request.image
+                );
                 error!("{}", report);
                 return Err(anyhow!(report));
             }
         };
-        let img = img.to_rgba8();
-        let texture_width = img.width();
-        let texture_height = img.height();
-        let image_texture = {
-            debug!("Trying to create and write to the texture ...");
-            let size = texture::texture_size(texture_width, texture_height);
-            let desc = texture::image_srgb_unorm_desc(None, size, 1);
-            let texture = self.device.create_texture(&desc);
-            self.queue.write_texture(
-                wgpu::TexelCopyTextureInfo {
-                    texture: &texture,
-                    mip_level: 0,
-                    origin: wgpu::Origin3d::ZERO,
-                    aspect: wgpu::TextureAspect::All,
-                },
-                &img,
-                wgpu::TexelCopyBufferLayout {
-                    offset: 0,
-                    bytes_per_row: Some(4 * texture_width),
-                    rows_per_image: Some(texture_height),
-                },
-                size,
-            );
+        request.compute_palette(&img.rgba);
+        Ok((request, img))
+    }
 
-            texture
-        };
+    /// Display the wallpaper loaded by `load_wallpaper`, and remember it.
+    async fn apply_wallpaper(
+        &mut self,
+        qh: &QueueHandle<Self>,
+        args: ImageArgs,
+        request: ContentArgs,
+        img: DecodedImage,
+        task_handle: Option<TaskHandle>,
+    ) -> Result<()> {
+        let targets = self.targets(&args.outputs)?;
+
+        // The request is finished once the transitions on all outputs are finished.
+        let task_handle = task_handle
+            .filter(|_| request.transition_options.no_interrupt)
+            .map(Arc::new);
+        self.show(qh, &request, &img, &targets, task_handle);
+
+        // Remember the new wallpaper, which is restored when the outputs are plugged in again.
+        if args.outputs.is_empty() {
+            // Outputs plugged in later display it as well.
+            self.output_requests.clear();
+            self.request = request;
+            self.img = img;
+        } else {
+            for name in &args.outputs {
+                self.output_requests.insert(name.clone(), request.clone());
+            }
+        }
 
-        // Set new fill color
-        self.fill_color = fill_rgb;
-
-        // If the new image is loaded, try to write the image path into the state file.
-        Self::save_image_path_to_restore_file(image_path, resize_option, self.fill_color).await;
-
-        // Set the new texture;
-        debug!("Set new texture for wallpaper ...");
-        self.image_texture = image_texture;
-        self.texture_width = texture_width;
-        self.texture_height = texture_height;
-
-        // Bind the new texture within the bind group.
-        debug!("Trying to bind the new texture and the sampler together ...");
-        let image_texture_view = self
-            .image_texture
-            .create_view(&texture::image_view_desc(None));
-        let layout = self
-            .device
-            .create_bind_group_layout(&bind_group::texture_and_sampler::layout_desc(None));
-        let bind_group = bind_group::texture_and_sampler::bind_group(
-            &self.device,
-            None,
-            &layout,
-            &image_texture_view,
-            &self.sampler,
-        );
-        self.bind_group = bind_group;
-
-        // Re-filling the vertex buffer.
-        self.resize_option = resize_option;
-        debug!("Re-filling the vertex buffer with the new resize option ...");
-        let vertex_buffer = vertex::create_vertex_buffer_with_resize_option(
-            (self.config.width, self.config.height),
-            (self.texture_width, self.texture_height),
-            self.resize_option,
-        );
-        self.queue
-            .write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&vertex_buffer));
+        // If the new image is loaded, try to write the state into the restore file.
+        self.save_restore_state().await;
+        self.update_watches();
 
-        // Request a new frame to draw the new wallpaper.
-        self.damaged = true;
-        let wl_surface = self.layer_surface.wl_surface().clone();
-        self.layer_surface.wl_surface().frame(qh, wl_surface);
-        self.layer_surface.commit();
+        if args.outputs.is_empty() {
+            self.export_palette().await;
+            self.run_hook(HookEvent::WallpaperChanged, None);
+        }
+        for name in &args.outputs {
+            self.run_hook(HookEvent::WallpaperChanged, Some(name));
+        }
 
         Ok(())
     }
 
-    /// Start a transition from the old wallpaper to the selected image. This function will change
-    /// both the stored wallpaper and change the resize option. If we encounter error after
-    /// changing the image, the following transition will be canceled and the final frame will be
-    /// drawn immediately.
-    #[tracing::instrument(skip(self, task_handle))]
-    #[allow(clippy::too_many_arguments)]
-    pub async fn start_transition(
+    /// Start a slideshow on all outputs, replacing the running one.
+    pub async fn start_slideshow(
         &mut self,
         qh: &QueueHandle<Self>,
-        img_path: &Path,
-        resize_option: server_cli::ResizeOption,
-        fill_rgb: (f64, f64, f64),
-        duration: f64,
-        fps: f64,
-        transition_kind: TransitionKind,
-        transition_options: TransitionOptions,
-        ease_kind: EaseKind,
-        task_handle: TaskHandle,
-    ) {
-        // Before we do any further rendering, grab the current buffer out for later use.
-        debug!("Saving the old wallpaper ...");
-        let old_texture_view = if let Some(interrupted_transition) = self.transition.take() {
-            debug!("Found on-fly transition! Saving its current frame instead ...");
-            interrupted_transition
-                .into_frame()
-                .create_view(&texture::image_view_desc(Some("Old transition frame")))
-        } else {
-            debug!("Re-rendering the old wallpaper ...");
-            let mut old_buffer = OffScreen::create(
-                &self.device,
-                (self.config.width, self.config.height),
-                self.config.format,
-            );
-            let mut encoder = self
-                .device
-                .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
-            {
-                old_buffer.update_pass(
-                    &self.device,
-                    &mut encoder,
-                    (self.config.width, self.config.height),
-                    self.fill_color,
-                    &self.render_pipeline,
-                    &[&self.bind_group],
-                    &self.vertex_buffer,
-                    &self.index_buffer,
-                    NUM_INDEX,
-                );
+        args: SlideshowArgs,
+    ) -> Result<()> {
+        let slideshow = tokio::task::spawn_blocking(move || Slideshow::new(args)).await??;
+        let args = slideshow.image_args();
+
+        // The running slideshow and the schedule are kept if the first image cannot be loaded.
+        let (request, img) = self.load_wallpaper(&args).await?;
+        self.slideshow = Some(slideshow);
+        if self.schedule.take().is_some() {
+            info!("The schedule is stopped by the slideshow");
+        }
+        self.apply_wallpaper(qh, args, request, img, None).await
+    }
+
+    pub async fn control_slideshow(
+        &mut self,
+        qh: &QueueHandle<Self>,
+        command: SlideshowCommand,
+    ) -> Result<()> {
+        let slideshow = self
+            .slideshow
+            .as_mut()
+            .ok_or(anyhow!("No slideshow is running"))?;
+        match command {
+            SlideshowCommand::Pause => slideshow.pause(),
+            SlideshowCommand::Resume => slideshow.resume_timer(),
+            SlideshowCommand::Next => slideshow.next(),
+            SlideshowCommand::Prev => slideshow.prev(),
+            SlideshowCommand::Stop => self.slideshow = None,
+        }
+
+        match (command, &self.slideshow) {
+            (SlideshowCommand::Next | SlideshowCommand::Prev, Some(slideshow)) => {
+                let args = slideshow.image_args();
+                self.set_wallpaper(qh, args, None).await
             }
-            self.queue.submit(Some(encoder.finish()));
-            old_buffer
-                .into_frame()
-                .create_view(&texture::image_view_desc(Some("Old texture view")))
+            _ => {
+                self.save_restore_state().await;
+                self.update_watches();
+                Ok(())
+            }
+        }
+    }
+
+    /// Cancel, pause, resume or move the running transitions of all outputs, or change the speed
+    /// of the running and later transitions.
+    pub fn control_transition(
+        &mut self,
+        qh: &QueueHandle<Self>,
+        command: TransitionCommand,
+    ) -> Result<()> {
+        if let TransitionCommand::Speed { speed } = command {
+            self.transition_speed = speed;
+        } else if !self.surfaces.iter().any(OutputSurface::has_transition) {
+            return Err(anyhow!("No transition is running"));
+        }
+
+        let mut cancelled = vec![];
+        for surface in &mut self.surfaces {
+            match command {
+                TransitionCommand::Cancel => {
+                    if surface.cancel_transition(qh) {
+                        cancelled.push(surface.name().map(str::to_owned));
+                    }
+                }
+                TransitionCommand::Pause => surface.pause_transition(),
+                TransitionCommand::Resume => surface.resume_transition(qh),
+                TransitionCommand::Seek { progress } => surface.seek_transition(qh, progress),
+                TransitionCommand::Speed { speed } => surface.set_transition_speed(speed),
+            }
+        }
+
+        for name in cancelled {
+            self.run_hook(HookEvent::TransitionFinished, name.as_deref());
+        }
+        Ok(())
+    }
+
+    /// When the slideshow displays the next image. `None` if no slideshow is running, or if it
+    /// is paused.
+    pub fn slideshow_deadline(&self) -> Option<tokio::time::Instant> {
+        self.slideshow.as_ref().and_then(Slideshow::deadline)
+    }
+
+    /// Display the next image of the slideshow. Images which cannot be loaded are skipped at the
+    /// next deadline.
+    pub async fn advance_slideshow(&mut self, qh: &QueueHandle<Self>) {
+        if let Err(e) = self.control_slideshow(qh, SlideshowCommand::Next).await {
+            error!("Failed to display the next image of the slideshow: {e}");
+        }
+    }
+
+    /// When the schedule is checked next. `None` if no schedule is running.
+    pub fn schedule_deadline(&self) -> Option<Instant> {
+        self.schedule.as_ref().and(self.schedule_deadline)
+    }
+
+    /// Display the scheduled wallpaper, if the schedule has changed it.
+    pub async fn advance_schedule(&mut self, qh: &QueueHandle<Self>) {
+        let Some(wallpaper) = self.check_schedule() else {
+            return;
         };
 
-        debug!("Loading the new image: {img_path:?}");
-        if let Err(e) = self
-            .change_image_and_request_frame(qh, img_path, resize_option, fill_rgb)
-            .await
-        {
-            error!("Failed to load the new image to create transition! : {e}");
+        debug!("Displaying the scheduled wallpaper: {:?}", wallpaper.image);
+        if let Err(e) = self.set_wallpaper(qh, wallpaper.image_args(), None).await {
+            error!("Failed to display the scheduled wallpaper: {e}");
+        }
+    }
+
+    /// The scheduled wallpaper, if it isn't displayed yet, and when to check the schedule next. A
+    /// wallpaper which cannot be loaded isn't tried again until the schedule changes it.
+    fn check_schedule(&mut self) -> Option<ScheduledWallpaper> {
+        let (wallpaper, remaining) = match self.schedule.as_ref()?.now() {
+            Some(current) => current,
+            // Nothing is scheduled around now, e.g. only sunrise entries during a polar night.
+            None => {
+                self.schedule_deadline = Some(Instant::now() + SCHEDULE_CHECK_INTERVAL);
+                return None;
+            }
+        };
+
+        self.schedule_deadline = Some(Instant::now() + remaining.min(SCHEDULE_CHECK_INTERVAL));
+        if self.scheduled.as_ref() == Some(&wallpaper.image) {
+            return None;
+        }
+        self.scheduled = Some(wallpaper.image.clone());
+        Some(wallpaper)
+    }
+
+    /// Fade in the wallpapers given on the command line or restored, from the fill color the
+    /// surfaces are mapped with. If the wallpaper cannot be loaded, the default image is faded in
+    /// instead, or the fill color is kept.
+    #[tracing::instrument(skip_all)]
+    pub async fn fade_in(&mut self, qh: &QueueHandle<Self>) {
+        // The restore file is kept for the next start if the wallpaper cannot be loaded, in case
+        // the image is on a drive which isn't mounted yet.
+        let mut save = false;
+
+        // The schedule decides the wallpaper of all outputs.
+        if let Some(wallpaper) = self.check_schedule() {
+            self.initial_request = Some(ContentArgs::from(&wallpaper.image_args()));
+            self.initial_output_requests.clear();
+        }
+
+        if let Some(request) = self.initial_request.take() {
+            let image = request.image.clone();
+            if let Some((mut request, img)) = self.load_or_default(request).await {
+                request.compute_palette(&img.rgba);
+                save = request.image == image;
+                let targets = self.targets(&[]).unwrap_or_default();
+                self.show(qh, &request, &img, &targets, None);
+                self.request = request;
+                self.img = img;
+            }
+        }
+
+        for (name, mut request) in std::mem::take(&mut self.initial_output_requests) {
+            // Outputs which are not plugged in get their wallpapers when they are.
+            if let Ok(targets) = self.targets(std::slice::from_ref(&name)) {
+                match self.decode_wallpaper(&request).await {
+                    Ok(img) => {
+                        request.compute_palette(&img.rgba);
+                        self.show(qh, &request, &img, &targets, None);
+                    }
+                    Err(e) => warn!("Failed to load the wallpaper of output {name}: {e}"),
+                }
+            }
+            self.output_requests.insert(name, request);
+        }
+
+        if let Some(position) = self.initial_slideshow.take() {
+            match tokio::task::spawn_blocking(move || Slideshow::resume(position)).await {
+                Ok(Ok(slideshow)) => self.slideshow = Some(slideshow),
+                Ok(Err(e)) => warn!("Failed to continue the slideshow: {e}"),
+                Err(e) => warn!("Failed to continue the slideshow: {e}"),
+            }
+        }
+
+        if save {
+            self.save_restore_state().await;
+        }
+        self.update_watches();
+        self.export_palette().await;
+    }
+
+    /// The palette of the wallpaper of `output`, or of all outputs if `None`.
+    pub fn palette(&self, output: Option<&str>) -> Result<Palette> {
+        let request = match output {
+            Some(name) => match self.output_requests.get(name) {
+                Some(request) => request,
+                None => {
+                    self.targets(&[name.to_owned()])?;
+                    &self.request
+                }
+            },
+            None => &self.request,
+        };
+
+        request
+            .palette
+            .clone()
+            .ok_or(anyhow!("The wallpaper isn't loaded yet"))
+    }
+
+    /// Capture the frames displayed on `output`, or on all outputs if `None`, including the frames
+    /// of running transitions. They are written to `path` as PNG if given, which is a directory to
+    /// write `<OUTPUT>.png` to if `output` is `None`, or sent in the reply otherwise.
+    pub async fn screenshot(
+        &self,
+        output: Option<String>,
+        path: Option<PathBuf>,
+    ) -> Result<Vec<ipc::Screenshot>> {
+        let targets = self.targets(output.as_slice())?;
+        if let (Some(dir), None) = (&path, &output) {
+            tokio::fs::create_dir_all(dir)
+                .await
+                .map_err(|e| anyhow!("Failed to create {dir:?}: {e}"))?;
+        }
+
+        let mut screenshots = vec![];
+        let surfaces = self
+            .surfaces
+            .iter()
+            .filter(|surface| targets.contains(&surface.output().id()));
+        for (i, surface) in surfaces.enumerate() {
+            let name = surface.name().map(str::to_owned);
+            let frame = surface.screenshot(&self.gpu).await.map_err(|e| {
+                anyhow!(
+                    "Failed to capture {}: {e}",
+                    name.as_deref().unwrap_or("the output")
+                )
+            })?;
+            let size = frame.dimensions();
+            let png = Self::encode_png(frame).await?;
+
+            let path = match (&path, &output) {
+                (None, _) => None,
+                (Some(path), Some(_)) => Some(path.clone()),
+                (Some(dir), None) => {
+                    let file_name = name.clone().unwrap_or_else(|| format!("output-{i}"));
+                    Some(dir.join(format!("{file_name}.png")))
+                }
+            };
+            let png = match &path {
+                Some(path) => {
+                    write_atomically(path, &png)
+                        .await
+                        .map_err(|e| anyhow!("Failed to write {path:?}: {e}"))?;
+                    None
+                }
+                None => Some(png),
+            };
+
+            screenshots.push(ipc::Screenshot {
+                output: name,
+                size,
+                path,
+                png,
+            });
+        }
+
+        Ok(screenshots)
+    }
+
+    /// Export the palette of the wallpaper of all outputs into the state directory, in the
+    /// configured formats.
+    async fn export_palette(&self) {
+        let Some(palette) = &self.request.palette else {
             return;
+        };
+
+        for &format in &self.config.export_palette {
+            let path = match server_cli::default_state_dir() {
+                Ok(dir) => dir.join(format.file_name()),
+                Err(e) => {
+                    error!("Failed to get the state directory: {e}");
+                    return;
+                }
+            };
+            if let Err(e) = write_atomically(&path, palette.export(format).as_bytes()).await {
+                error!("Failed to export the palette to {path:?}: {e}");
+            }
         }
+    }
 
-        // The transition shader need the final texture view, so we need to render the new image to
-        // the off-screen buffer. Then we can get the texture view from the off-screen buffer.
-        debug!("Rendering the new image to the off-screen buffer ...");
-        let mut encoder = self
-            .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
-        self.off_screen_buffer.update_pass(
-            &self.device,
-            &mut encoder,
-            (self.config.width, self.config.height),
-            self.fill_color,
-            &self.render_pipeline,
-            &[&self.bind_group],
-            &self.vertex_buffer,
-            &self.index_buffer,
-            NUM_INDEX,
-        );
-        self.queue.submit(Some(encoder.finish()));
-
-        let new_texture_view = self
-            .off_screen_buffer
-            .current_frame()
-            .create_view(&texture::image_view_desc(Some("New texture view")));
-
-        let now = std::time::Instant::now();
-        debug!("Transition meta data: now={now:?}, duration={duration}, fps={fps}");
-
-        let transition = match shaders::transition::create_transition(
-            &self.device,
-            // Render to off-screen buffer first
-            OffScreen::format(),
-            old_texture_view,
-            new_texture_view,
-            transition_kind,
-            transition_options,
-        ) {
-            Some(t) => t,
-            // Because we already request a new frame, the final frame will be drawn in the next
-            // frame.
-            None => return,
+    /// Watch the directories of the displayed images and of the slideshow, as configured.
+    fn update_watches(&mut self) {
+        let Some(file_watcher) = &mut self.file_watcher else {
+            return;
+        };
+
+        let mut dirs = HashMap::new();
+        if self.config.watch.images {
+            let requests = std::iter::once(&self.request).chain(self.output_requests.values());
+            for request in requests {
+                if let ImageSource::Path(path) = &request.image
+                    && let Some(dir) = path.parent()
+                {
+                    dirs.insert(dir.to_owned(), RecursiveMode::NonRecursive);
+                }
+            }
+        }
+        if self.config.watch.directories
+            && let Some(slideshow) = &self.slideshow
+        {
+            for (dir, recursive) in slideshow.directories() {
+                let mode = match recursive {
+                    true => RecursiveMode::Recursive,
+                    false => RecursiveMode::NonRecursive,
+                };
+                dirs.insert(dir.to_owned(), mode);
+            }
+        }
+
+        file_watcher.watch(dirs);
+    }
+
+    /// Remember the watched files changed by `event`. They are loaded once they stay unchanged for
+    /// the debounce time, so that half-written files are not loaded.
+    pub fn file_changed(&mut self, event: notify::Event) {
+        for path in watcher::changed_files(&event) {
+            if self.is_displayed(path) || self.is_new_slideshow_image(path) {
+                debug!("{path:?} is changed");
+                self.changed_files.insert(path.clone());
+                self.watch_deadline = Some(Instant::now() + self.config.watch.debounce());
+            }
+        }
+    }
+
+    /// When the changed files are loaded. `None` if no watched file is changed.
+    pub fn watch_deadline(&self) -> Option<Instant> {
+        self.watch_deadline
+    }
+
+    /// Reload the changed images which are displayed, and add the new images to the slideshow.
+    pub async fn apply_file_changes(&mut self, qh: &QueueHandle<Self>) {
+        self.watch_deadline = None;
+        for path in std::mem::take(&mut self.changed_files) {
+            if self.is_displayed(&path) {
+                self.reload_image(qh, &path).await;
+            } else if self.is_new_slideshow_image(&path)
+                && path.is_file()
+                && let Some(slideshow) = &mut self.slideshow
+                && slideshow.add(path.clone())
+            {
+                info!("{path:?} is added to the slideshow");
+            }
+        }
+    }
+
+    fn is_displayed(&self, path: &Path) -> bool {
+        let image = ImageSource::Path(path.to_owned());
+        self.config.watch.images
+            && (self.request.image == image
+                || self
+                    .output_requests
+                    .values()
+                    .any(|request| request.image == image))
+    }
+
+    fn is_new_slideshow_image(&self, path: &Path) -> bool {
+        let Some(slideshow) = self
+            .slideshow
+            .as_ref()
+            .filter(|_| self.config.watch.directories)
+        else {
+            return false;
         };
 
-        let easing_function = ease::create_easing_curve(ease_kind);
-        let transition = TransitionState::new(
-            &self.device,
-            now,
-            duration,
-            fps,
-            transition,
-            easing_function,
-            (self.config.width, self.config.height),
-            self.config.format,
-            if !transition_options.no_interrupt {
-                None
-            } else {
-                Some(task_handle)
+        image::ImageFormat::from_path(path).is_ok()
+            && slideshow
+                .directories()
+                .any(|(dir, recursive)| watcher::is_in(path, dir, recursive))
+    }
+
+    /// Display the image at `path` again on the outputs displaying it, with the transition
+    /// configured in `[watch]`, or the default one. If it cannot be loaded, the old one is kept.
+    async fn reload_image(&mut self, qh: &QueueHandle<Self>, path: &Path) {
+        let image = ImageSource::Path(path.to_owned());
+        let rgba = match Self::decode_image(image.clone()).await {
+            Ok(rgba) => Arc::new(rgba),
+            Err(e) => {
+                warn!("Failed to reload {path:?}: {e}");
+                return;
+            }
+        };
+        info!("Reloading {path:?} ...");
+
+        if self.request.image == image {
+            let img = match self.prepare_paddings(rgba.clone(), &self.request).await {
+                Ok(img) => img,
+                Err(e) => {
+                    warn!("Failed to reload {path:?}: {e}");
+                    return;
+                }
+            };
+            self.request.compute_palette(&img.rgba);
+            let targets = self
+                .surfaces
+                .iter()
+                .filter(|surface| {
+                    surface
+                        .name()
+                        .is_none_or(|name| !self.output_requests.contains_key(name))
+                })
+                .map(|surface| surface.output().id())
+                .collect::<Vec<_>>();
+            let request = self.watch_request(&self.request);
+            self.show(qh, &request, &img, &targets, None);
+            self.img = img;
+            self.export_palette().await;
+            self.run_hook(HookEvent::WallpaperChanged, None);
+        }
+
+        let mut changed = vec![];
+        for (name, request) in &mut self.output_requests {
+            if request.image == image {
+                request.compute_palette(&rgba);
+                changed.push(name.clone());
+            }
+        }
+        for name in changed {
+            let request = self.watch_request(&self.output_requests[&name]);
+            // Outputs which are not plugged in load it when they are.
+            if let Ok(targets) = self.targets(std::slice::from_ref(&name)) {
+                match self.prepare_paddings(rgba.clone(), &request).await {
+                    Ok(img) => self.show(qh, &request, &img, &targets, None),
+                    Err(e) => warn!("Failed to reload {path:?} on output {name}: {e}"),
+                }
+            }
+            self.run_hook(HookEvent::WallpaperChanged, Some(&name));
+        }
+    }
+
+    /// Run the hook of `event` about the wallpaper of `output`, or of all outputs if `None`.
+    pub fn run_hook(&self, event: HookEvent, output: Option<&str>) -> Option<JoinHandle<()>> {
+        let (image_output, request) = match output.and_then(|name| self.output_requests.get(name)) {
+            Some(request) => (output, request),
+            None => (None, &self.request),
+        };
+        let defaults = self.config.defaults_for(output);
+
+        let mut vars = vec![];
+        if let Some(output) = output {
+            vars.push(("PWWW_OUTPUT", output.to_owned()));
+        }
+        let image = match &request.image {
+            ImageSource::Path(path) => Some(path.clone()),
+            // Image data is in the restore image file, which is written before hooks are run.
+            ImageSource::Data(_) => server_cli::default_restore_image_path(image_output).ok(),
+            ImageSource::Color(_) | ImageSource::Gradient(_) => None,
+        };
+        if let Some(image) = &image {
+            vars.push(("PWWW_IMAGE", image.display().to_string()));
+        }
+        vars.push(("PWWW_RESIZE", value_name(defaults.resize(request.resize))));
+        let (r, g, b) = request.fill_rgb(&defaults);
+        vars.push(("PWWW_FILL_RGB", format!("{r:02x}{g:02x}{b:02x}")));
+        vars.push((
+            "PWWW_TRANSITION",
+            value_name(defaults.transition(request.transition)),
+        ));
+        if let Some(palette) = &request.palette {
+            let colors = palette.colors.iter().map(|color| palette::hex(color.rgb));
+            vars.push(("PWWW_PALETTE", colors.collect::<Vec<_>>().join(" ")));
+            vars.push(("PWWW_BACKGROUND", palette::hex(palette.background)));
+            vars.push(("PWWW_FOREGROUND", palette::hex(palette.foreground)));
+            vars.push(("PWWW_LUMINANCE", format!("{:.3}", palette.luminance)));
+        }
+
+        // All the options, as they are saved into the restore file.
+        let source = image.map_or(request.image.clone(), ImageSource::Path);
+        match serde_json::to_string(&request.to_restore(&defaults, source)) {
+            Ok(options) => vars.push(("PWWW_OPTIONS", options)),
+            Err(e) => warn!("Failed to serialize the options for the hook of {event}: {e}"),
+        }
+
+        hooks::run(&self.config.hooks, event, vars)
+    }
+
+    /// `request` with the transition into reloaded images.
+    fn watch_request(&self, request: &ContentArgs) -> ContentArgs {
+        ContentArgs {
+            transition: self.config.watch.transition,
+            transition_options: TransitionOptions {
+                duration: self.config.watch.transition_duration,
+                ..TransitionOptions::default()
             },
-        );
+            ease: None,
+            ..request.clone()
+        }
+    }
+
+    /// Decode the wallpaper of `request`, or the default image if it cannot be decoded. `None` if
+    /// neither can be decoded.
+    async fn load_or_default(&self, request: ContentArgs) -> Option<(ContentArgs, DecodedImage)> {
+        match self.decode_wallpaper(&request).await {
+            Ok(img) => return Some((request, img)),
+            Err(e) => warn!("Failed to load the wallpaper {:?}: {e}", request.image),
+        }
 
-        if self.transition.replace(transition).is_some() {
-            // Anyway, if `TaskHub` functions correctly or the old transition is taken by this
-            // function already, we won't find unfinished transitions here.
-            error!(
-                "Found unfinished transition! The old one will be finished immediately\
-                and start a new one!"
-            );
+        if let Some(path) = &self.config.default_image {
+            let default = ContentArgs {
+                transition: request.transition,
+                transition_options: request.transition_options,
+                ease: request.ease,
+                ..ContentArgs::new(ImageSource::Path(path.clone()))
+            };
+            if default.image != request.image {
+                match self.decode_wallpaper(&default).await {
+                    Ok(img) => {
+                        warn!("Falling back to the default image {path:?}");
+                        return Some((default, img));
+                    }
+                    Err(e) => warn!("Failed to load the default image {path:?}: {e}"),
+                }
+            }
         }
+
+        warn!("Falling back to the fill color");
+        None
     }
 
-    #[tracing::instrument(skip(self, transition_state, encoder, target_view))]
-    fn draw_transition(
+    /// Display `request` on the surfaces of `targets`, with the transition decided by the defaults
+    /// of each output.
+    fn show(
         &mut self,
-        transition_state: &mut TransitionState,
-        encoder: &mut wgpu::CommandEncoder,
-        target_view: &wgpu::TextureView,
-    ) -> Result<(), TransitionRenderError> {
-        let now = std::time::Instant::now();
-        transition_state.render_pass(&self.device, encoder, now, target_view, self.fill_color)
-    }
-
-    #[tracing::instrument]
-    async fn save_image_path_to_restore_file(
-        path: &Path,
-        resize_option: server_cli::ResizeOption,
-        fill_rgb: (f64, f64, f64),
+        qh: &QueueHandle<Self>,
+        request: &ContentArgs,
+        img: &DecodedImage,
+        targets: &[ObjectId],
+        task_handle: Option<Arc<TaskHandle>>,
     ) {
-        match server_cli::default_restore_path() {
-            Err(e) => error!("Failed to get restore file path: {e}"),
-            Ok(restore_file_path) => {
-                let rgb = utils::rgb_f64_to_u8(fill_rgb);
-                let restore = Restore::new(path, resize_option, rgb);
-
-                let mut buf = vec![];
-                if let Err(e) = restore.serialize_to(&mut buf) {
-                    error!("Failed to deserialize restore option: {e}");
-                    return;
+        for surface in self
+            .surfaces
+            .iter_mut()
+            .filter(|surface| targets.contains(&surface.output().id()))
+        {
+            let defaults = self.config.defaults_for(surface.name());
+            let content = request.resolve(&defaults);
+            match defaults.transition(request.transition) {
+                TransitionKind::No => {
+                    surface.change_image_and_request_frame(&self.gpu, qh, content, img)
+                }
+                transition_kind => {
+                    let options = request.transition_options;
+                    surface.start_transition(
+                        &self.gpu,
+                        qh,
+                        content,
+                        img,
+                        defaults.transition_duration(options.duration),
+                        defaults.transition_fps(options.fps),
+                        transition_kind,
+                        TransitionOptions {
+                            wipe_angle: Some(defaults.wipe_angle(options.wipe_angle)),
+                            ..options
+                        },
+                        defaults.ease(request.ease),
+                        task_handle.clone(),
+                    )
                 }
+            }
+        }
+    }
 
-                if let Err(e) = tokio::fs::write(restore_file_path, &buf).await {
-                    error!("Failed to write image path to restore file: {e}");
+    /// Decode and display the wallpapers remembered for the outputs plugged in since the last call.
+    /// The wallpaper of all outputs is displayed instead if one cannot be decoded.
+    pub async fn show_pending_outputs(&mut self, qh: &QueueHandle<Self>) {
+        for name in std::mem::take(&mut self.pending_outputs) {
+            // The output may be unplugged again in the meantime.
+            let Ok(targets) = self.targets(std::slice::from_ref(&name)) else {
+                continue;
+            };
+            let Some(mut request) = self.output_requests.get(&name).cloned() else {
+                continue;
+            };
+
+            match self.decode_wallpaper(&request).await {
+                Ok(img) => {
+                    request.compute_palette(&img.rgba);
+                    self.show(qh, &request, &img, &targets, None);
+                    self.output_requests.insert(name, request);
+                }
+                Err(e) => {
+                    warn!("Failed to load the wallpaper of output {name}: {e}");
+                    let (request, img) = (self.request.clone(), self.img.clone());
+                    self.show(qh, &request, &img, &targets, None);
                 }
             }
         }
+    }
+
+    /// Apply a new configuration in place, re-rendering the outputs whose wallpapers look
+    /// different with it. Return the options which cannot change without restarting the daemon.
+    #[tracing::instrument(skip_all)]
+    pub async fn reload(&mut self, qh: &QueueHandle<Self>, mut config: Config) -> Vec<String> {
+        let mut not_applied = vec![];
+        if config.socket != self.config.socket {
+            not_applied.push("socket".to_string());
+            config.socket = self.config.socket.clone();
+        }
+        // A changed schedule is started over, even if it was stopped.
+        if config.schedule != self.config.schedule {
+            self.schedule = match &config.schedule {
+                Some(schedule) => load_schedule(schedule.clone()).await,
+                None => None,
+            };
+            self.schedule_deadline = Some(Instant::now());
+            self.scheduled = None;
+            if self.schedule.is_some() && self.slideshow.take().is_some() {
+                info!("The slideshow is stopped by the schedule");
+            }
+        }
+        self.config = config;
+        self.update_watches();
+
+        // Paddings may be filled differently now, from other copies of the image.
+        match self
+            .prepare_paddings(self.img.rgba.clone(), &self.request)
+            .await
+        {
+            Ok(img) => self.img = img,
+            Err(e) => error!("Failed to prepare the paddings of the wallpaper: {e}"),
+        }
+
+        for i in 0..self.surfaces.len() {
+            let surface = &self.surfaces[i];
+            let defaults = self.config.defaults_for(surface.name());
+            let remembered = surface
+                .name()
+                .and_then(|name| self.output_requests.get(name))
+                .cloned();
+            let content = remembered
+                .as_ref()
+                .unwrap_or(&self.request)
+                .resolve(&defaults);
+            if *surface.content() == content {
+                continue;
+            }
 
-        debug!("Image path wrote to restore file: {path:?}");
+            debug!("Re-rendering output {:?} ...", surface.name());
+            let img = match remembered {
+                Some(request) => match self.decode_wallpaper(&request).await {
+                    Ok(img) => img,
+                    Err(e) => {
+                        error!(
+                            "Failed to reload the wallpaper of {:?}: {e}",
+                            self.surfaces[i].name()
+                        );
+                        continue;
+                    }
+                },
+                None => self.img.clone(),
+            };
+            self.surfaces[i].change_image_and_request_frame(&self.gpu, qh, content, &img);
+        }
+
+        not_applied
+    }
+
+    /// Find the outputs named `outputs`, or all outputs if it's empty.
+    fn targets(&self, outputs: &[String]) -> Result<Vec<ObjectId>> {
+        if outputs.is_empty() {
+            return Ok(self
+                .surfaces
+                .iter()
+                .map(|surface| surface.output().id())
+                .collect());
+        }
+
+        outputs
+            .iter()
+            .map(|name| {
+                self.surfaces
+                    .iter()
+                    .find(|surface| surface.name() == Some(name.as_str()))
+                    .map(|surface| surface.output().id())
+                    .ok_or(anyhow!("No such output: `{name}`"))
+            })
+            .collect()
+    }
+
+    /// Every known output, and the wallpaper shown on it.
+    pub fn outputs(&self) -> Vec<ipc::OutputInfo> {
+        self.output_state
+            .outputs()
+            .filter_map(|output| {
+                let info = self.output_state.info(&output)?;
+                let surface = self
+                    .surfaces
+                    .iter()
+                    .find(|surface| *surface.output() == output);
+                let mode =
+                    info.modes
+                        .iter()
+                        .find(|mode| mode.current)
+                        .map(|mode| ipc::OutputMode {
+                            width: mode.dimensions.0,
+                            height: mode.dimensions.1,
+                            refresh: mode.refresh_rate,
+                        });
+
+                Some(ipc::OutputInfo {
+                    name: info.name,
+                    description: info.description,
+                    make: info.make,
+                    model: info.model,
+                    logical_position: info.logical_position,
+                    logical_size: info.logical_size,
+                    mode,
+                    scale: surface
+                        .map(|surface| surface.scale_factor())
+                        .unwrap_or(info.scale_factor as f64),
+                    transform: transform_name(info.transform).to_string(),
+                    wallpaper: surface.map(|surface| surface.content().image.summary()),
+                    resize: surface.map(|surface| surface.content().resize_option),
+                })
+            })
+            .collect()
+    }
+
+    /// Find the output surface of `surface`.
+    fn find_surface<'a>(
+        surfaces: &'a mut [OutputSurface],
+        surface: &WlSurface,
+    ) -> Option<&'a mut OutputSurface> {
+        let found = surfaces
+            .iter_mut()
+            .find(|output_surface| output_surface.wl_surface() == surface);
+        if found.is_none() {
+            warn!("The surface doesn't match any surface stored in `Wallpaper`!");
+        }
+        found
+    }
+
+    fn preferred_scale_changed(
+        &mut self,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+        surface: &WlSurface,
+        scale: u32,
+    ) {
+        if let Some(surface) = Self::find_surface(&mut self.surfaces, surface) {
+            surface.set_preferred_scale(&self.gpu, qh, scale);
+        }
+    }
+
+    /// The fill color, displayed when there is no wallpaper.
+    fn fill_color(config: &Config) -> ContentArgs {
+        let rgb = config.defaults.fill_rgb(None, None);
+        let mut request = ContentArgs {
+            resize: Some(server_cli::ResizeOption::Stretch),
+            fill_rgb: Some(rgb),
+            padding: Padding::from(Fill::Color),
+            ..ContentArgs::new(ImageSource::Color(rgb))
+        };
+        request.palette = Some(Palette::from_colors(&[rgb]));
+        request
+    }
+
+    /// Encode a screenshot on a blocking thread, so that the event loop isn't blocked by large
+    /// outputs.
+    async fn encode_png(frame: RgbaImage) -> Result<Vec<u8>> {
+        tokio::task::spawn_blocking(move || {
+            let mut png = vec![];
+            frame.write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)?;
+            Ok(png)
+        })
+        .await?
+    }
+
+    /// Decode the image on a blocking thread, so that the event loop isn't blocked by large
+    /// images.
+    async fn decode_image(image: ImageSource) -> Result<RgbaImage> {
+        tokio::task::spawn_blocking(move || Self::load_image(&image)).await?
+    }
+
+    /// Decode the image of `request`, along with the copies of it drawn in its paddings.
+    async fn decode_wallpaper(&self, request: &ContentArgs) -> Result<DecodedImage> {
+        let rgba = Self::decode_image(request.image.clone()).await?;
+        self.prepare_paddings(Arc::new(rgba), request).await
+    }
+
+    /// Make the copies of `rgba` drawn in the paddings of `request` on a blocking thread. They are
+    /// made for the fills of all outputs, so that they are ready for outputs plugged in later.
+    async fn prepare_paddings(
+        &self,
+        rgba: Arc<RgbaImage>,
+        request: &ContentArgs,
+    ) -> Result<DecodedImage> {
+        let fills = std::iter::once(None)
+            .chain(self.config.output.keys().map(|name| Some(name.as_str())))
+            .map(|name| self.config.defaults_for(name).fill(&request.padding))
+            .collect::<Vec<_>>();
+        let source_transform = request.source_transform;
+
+        let paddings = {
+            let rgba = rgba.clone();
+            tokio::task::spawn_blocking(move || PaddingImages::new(&rgba, &fills, source_transform))
+                .await?
+        };
+        Ok(DecodedImage {
+            rgba,
+            paddings: Arc::new(paddings),
+        })
+    }
+
+    /// Decode the image. Procedural wallpapers are not backed by any image, so a placeholder which
+    /// is never drawn is returned for them.
+    fn load_image(image: &ImageSource) -> Result<image::RgbaImage> {
+        let img = match image {
+            ImageSource::Path(path) => image::open(path)?,
+            ImageSource::Data(data) => image::load_from_memory(data)?,
+            ImageSource::Color(_) | ImageSource::Gradient(_) => {
+                return Ok(image::RgbaImage::new(1, 1));
+            }
+        };
+
+        Ok(img.to_rgba8())
+    }
+
+    /// Save the wallpapers of all outputs into the restore file. Image data sent through the
+    /// socket is written into the state directory first, because there is no path we can restore
+    /// it from.
+    #[tracing::instrument(skip_all)]
+    async fn save_restore_state(&self) {
+        let wallpaper = match Self::restore_source(&self.request.image, None).await {
+            Ok(source) => self.request.to_restore(&self.config.defaults, source),
+            Err(e) => {
+                error!("Failed to save the wallpaper into the restore file: {e}");
+                return;
+            }
+        };
+
+        let mut state = RestoreState::new(wallpaper);
+        state.slideshow = self.slideshow.as_ref().map(Slideshow::position);
+        for (name, request) in &self.output_requests {
+            match Self::restore_source(&request.image, Some(name)).await {
+                Ok(source) => {
+                    let defaults = self.config.defaults_for(Some(name));
+                    let restore = request.to_restore(&defaults, source);
+                    state.outputs.insert(name.clone(), restore);
+                }
+                Err(e) => error!(
+                    "Failed to save the wallpaper of output {name} into the restore file: {e}"
+                ),
+            }
+        }
+
+        let restore_file_path = match server_cli::default_restore_path() {
+            Ok(path) => path,
+            Err(e) => {
+                error!("Failed to get restore file path: {e}");
+                return;
+            }
+        };
+
+        let mut buf = vec![];
+        if let Err(e) = state.serialize_to(&mut buf) {
+            error!("Failed to serialize restore state: {e}");
+            return;
+        }
+
+        if let Err(e) = write_atomically(&restore_file_path, &buf).await {
+            error!("Failed to write restore file: {e}");
+            return;
+        }
+
+        debug!("State wrote to restore file: {state:?}");
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn save_history(&self) {
+        let history_path = match server_cli::default_history_path() {
+            Ok(path) => path,
+            Err(e) => {
+                error!("Failed to get history file path: {e}");
+                return;
+            }
+        };
+
+        let mut buf = vec![];
+        if let Err(e) = self.history.serialize_to(&mut buf) {
+            error!("Failed to serialize history: {e}");
+            return;
+        }
+
+        if let Err(e) = write_atomically(&history_path, &buf).await {
+            error!("Failed to write history file: {e}");
+        }
+    }
+
+    /// Where the image can be restored from. Image data is written into the restore image file of
+    /// `output`.
+    async fn restore_source(image: &ImageSource, output: Option<&str>) -> Result<ImageSource> {
+        match image {
+            ImageSource::Data(data) => {
+                let image_path = server_cli::default_restore_image_path(output)
+                    .map_err(|e| anyhow!("Failed to get restore image path: {e}"))?;
+                write_atomically(&image_path, data).await.map_err(|e| {
+                    anyhow!("Failed to write image data to restore image file: {e}")
+                })?;
+
+                Ok(ImageSource::Path(image_path))
+            }
+            image => Ok(image.clone()),
+        }
+    }
+}
+
+/// The name of `value` on the command line, e.g. `integer-crop`.
+fn value_name(value: impl clap::ValueEnum) -> String {
+    value
+        .to_possible_value()
+        .map(|value| value.get_name().to_string())
+        .unwrap_or_default()
+}
+
+/// Load the schedule of the config file on a blocking thread, because a GNOME background file may
+/// be read. A schedule which cannot be loaded isn't run.
+async fn load_schedule(config: ScheduleConfig) -> Option<Schedule> {
+    match tokio::task::spawn_blocking(move || Schedule::load(&config)).await {
+        Ok(Ok(schedule)) => Some(schedule),
+        Ok(Err(e)) => {
+            error!("Failed to load the schedule: {e}");
+            None
+        }
+        Err(e) => {
+            error!("Failed to load the schedule: {e}");
+            None
+        }
+    }
+}
+
+/// Write `data` into a temporary file next to `path`, then rename it to `path`, so that `path`
+/// never contains partially written data even if the daemon crashes.
+async fn write_atomically(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+
+    let mut file = tokio::fs::File::create(&tmp_path).await?;
+    file.write_all(data).await?;
+    file.sync_all().await?;
+    drop(file);
+
+    tokio::fs::rename(&tmp_path, path).await
+}
+
+/// Name of the transform in `wl_output` terms, i.e. counter-clockwise degrees.
+fn transform_name(transform: Transform) -> &'static str {
+    match transform {
+        Transform::Normal => "normal",
+        Transform::_90 => "90",
+        Transform::_180 => "180",
+        Transform::_270 => "270",
+        Transform::Flipped => "flipped",
+        Transform::Flipped90 => "flipped-90",
+        Transform::Flipped180 => "flipped-180",
+        Transform::Flipped270 => "flipped-270",
+        _ => "unknown",
     }
 }
 
@@ -768,11 +1660,51 @@ impl OutputHandler for Wallpaper {
 
     fn new_output(
         &mut self,
-        _conn: &wayland_client::Connection,
-        _qh: &wayland_client::QueueHandle<Self>,
-        _output: wayland_client::protocol::wl_output::WlOutput,
+        conn: &wayland_client::Connection,
+        qh: &wayland_client::QueueHandle<Self>,
+        output: wayland_client::protocol::wl_output::WlOutput,
     ) {
-        debug!("`new_output` triggered");
+        let name = self.output_state.info(&output).and_then(|info| info.name);
+        debug!("`new_output` triggered: {name:?}");
+
+        // The wallpaper set for this output before it was unplugged, if any, is decoded by
+        // `show_pending_outputs` outside of the Wayland dispatch. Until then, the surface is
+        // mapped with the fill color.
+        let pending = name
+            .clone()
+            .filter(|name| self.output_requests.contains_key(name));
+        let placeholder;
+        let (request, img) = match &pending {
+            Some(_) => {
+                placeholder = (Self::fill_color(&self.config), DecodedImage::placeholder());
+                (&placeholder.0, &placeholder.1)
+            }
+            None => (&self.request, &self.img),
+        };
+        let defaults = self.config.defaults_for(name.as_deref());
+
+        match OutputSurface::new(
+            &self.gpu,
+            conn,
+            qh,
+            &self.compositor_state,
+            &self.layer_shell_state,
+            &self.scale_state,
+            self.layer_options,
+            output,
+            name,
+            self.namespace.clone(),
+            request.resolve(&defaults),
+            img,
+        ) {
+            Ok(mut surface) => {
+                surface.set_transition_speed(self.transition_speed);
+                self.run_hook(HookEvent::OutputAdded, surface.name());
+                self.surfaces.push(surface);
+                self.pending_outputs.extend(pending);
+            }
+            Err(e) => error!("Failed to create the surface for the new output: {e}"),
+        }
     }
 
     fn update_output(
@@ -788,9 +1720,12 @@ impl OutputHandler for Wallpaper {
         &mut self,
         _conn: &wayland_client::Connection,
         _qh: &wayland_client::QueueHandle<Self>,
-        _output: wayland_client::protocol::wl_output::WlOutput,
+        output: wayland_client::protocol::wl_output::WlOutput,
     ) {
         debug!("`output_destroyed` triggered");
+
+        // Its wallpaper is still remembered in `output_requests`.
+        self.surfaces.retain(|surface| *surface.output() != output);
     }
 }
 
@@ -798,32 +1733,46 @@ impl CompositorHandler for Wallpaper {
     fn scale_factor_changed(
         &mut self,
         _conn: &wayland_client::Connection,
-        _qh: &wayland_client::QueueHandle<Self>,
-        _surface: &wayland_client::protocol::wl_surface::WlSurface,
-        _new_factor: i32,
+        qh: &wayland_client::QueueHandle<Self>,
+        surface: &wayland_client::protocol::wl_surface::WlSurface,
+        new_factor: i32,
     ) {
-        debug!("`scale_factor_changed` triggered");
+        debug!("`scale_factor_changed` triggered: {new_factor}");
+
+        if let Some(surface) = Self::find_surface(&mut self.surfaces, surface) {
+            surface.set_buffer_scale(&self.gpu, qh, new_factor);
+        }
     }
 
     fn transform_changed(
         &mut self,
         _conn: &wayland_client::Connection,
-        _qh: &wayland_client::QueueHandle<Self>,
-        _surface: &wayland_client::protocol::wl_surface::WlSurface,
-        _new_transform: wayland_client::protocol::wl_output::Transform,
+        qh: &wayland_client::QueueHandle<Self>,
+        surface: &wayland_client::protocol::wl_surface::WlSurface,
+        new_transform: wayland_client::protocol::wl_output::Transform,
     ) {
-        debug!("`transform_changed` triggered");
+        debug!("`transform_changed` triggered: {new_transform:?}");
+
+        if let Some(surface) = Self::find_surface(&mut self.surfaces, surface) {
+            surface.set_transform(&self.gpu, qh, new_transform);
+        }
     }
 
     fn frame(
         &mut self,
-        conn: &wayland_client::Connection,
+        _conn: &wayland_client::Connection,
         qh: &wayland_client::QueueHandle<Self>,
-        _surface: &wayland_client::protocol::wl_surface::WlSurface,
+        surface: &wayland_client::protocol::wl_surface::WlSurface,
         _time: u32,
     ) {
         debug!("`frame` triggered");
-        self.draw(conn, qh);
+
+        if let Some(surface) = Self::find_surface(&mut self.surfaces, surface)
+            && surface.draw(&self.gpu, qh)
+        {
+            let name = surface.name().map(str::to_owned);
+            self.run_hook(HookEvent::TransitionFinished, name.as_deref());
+        }
     }
 
     fn surface_enter(
@@ -858,15 +1807,18 @@ impl LayerShellHandler for Wallpaper {
         &mut self,
         _conn: &wayland_client::Connection,
         _qh: &wayland_client::QueueHandle<Self>,
-        _layer: &LayerSurface,
+        layer: &LayerSurface,
     ) {
-        debug!("`closed` triggered. Exiting ...");
-        self.exited = true;
+        // The compositor closes the surface when its output is gone, but the other outputs still
+        // need their wallpapers.
+        debug!("`closed` triggered. Dropping the surface ...");
+        self.surfaces
+            .retain(|surface| surface.layer_surface() != layer);
     }
 
     fn configure(
         &mut self,
-        conn: &wayland_client::Connection,
+        _conn: &wayland_client::Connection,
         qh: &wayland_client::QueueHandle<Self>,
         layer: &LayerSurface,
         configure: smithay_client_toolkit::shell::wlr_layer::LayerSurfaceConfigure,
@@ -874,13 +1826,8 @@ impl LayerShellHandler for Wallpaper {
     ) {
         debug!("`configure` triggered");
 
-        if self.layer_surface != *layer {
-            warn!("The layer doesn't match the surface stored in `Wallpaper`!");
-            return;
+        if let Some(surface) = Self::find_surface(&mut self.surfaces, layer.wl_surface()) {
+            surface.configure(&self.gpu, qh, configure.new_size);
         }
-
-        self.damaged = true;
-        self.config(configure, conn, qh);
-        self.draw(conn, qh);
     }
 }
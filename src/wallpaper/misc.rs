@@ -37,12 +37,12 @@ pub fn layer_surface_to_wgpu_surface(
 }
 
 pub fn adapter_options<'a>(
-    surface: &'a wgpu::Surface<'static>,
+    surface: Option<&'a wgpu::Surface<'static>>,
 ) -> wgpu::RequestAdapterOptions<'a, 'static> {
     wgpu::RequestAdapterOptions {
         power_preference: wgpu::PowerPreference::default(),
         force_fallback_adapter: false,
-        compatible_surface: Some(surface),
+        compatible_surface: surface,
     }
 }
 
@@ -2,9 +2,22 @@ use crate::wallpaper::{
     MAG_FILTER, MIN_FILTER, MIPMAP_FILTER, bind_group, render_pipeline, sampler, shaders, texture,
     vertex,
 };
+use anyhow::{Result, anyhow};
+use image::RgbaImage;
+use tokio::sync::oneshot;
 use tracing::debug;
+use wayland_client::protocol::wl_output::Transform;
 use wgpu::{self, util::DeviceExt};
 
+/// One indexed draw call in `OffScreen::update_pass`.
+pub struct Draw<'a> {
+    pub render_pipeline: &'a wgpu::RenderPipeline,
+    pub bind_group: &'a wgpu::BindGroup,
+    pub vertex_buffer: &'a wgpu::Buffer,
+    pub index_buffer: &'a wgpu::Buffer,
+    pub index_count: u32,
+}
+
 pub struct OffScreen {
     frame: wgpu::Texture,
 
@@ -132,38 +145,31 @@ impl OffScreen {
         }
     }
 
-    /// Update the content of off-screen rendering buffer. Re-create the buffer if the new size
-    /// doesn't equal to the current size.
-    ///
-    /// This `render_pass` will use `wallpaper.wgsl` shader.
-    #[tracing::instrument(skip(
-        self,
-        device,
-        encoder,
-        render_pipeline,
-        bind_groups,
-        vertex_buffer,
-        index_buffer,
-        index_buffer_len
-    ))]
-    #[allow(clippy::too_many_arguments)]
-    pub fn update_pass(
-        &mut self,
-        device: &wgpu::Device,
-        encoder: &mut wgpu::CommandEncoder,
-        surface_size: (u32, u32),
-        default_color: (f64, f64, f64),
-        render_pipeline: &wgpu::RenderPipeline,
-        bind_groups: &[&wgpu::BindGroup],
-        vertex_buffer: &wgpu::Buffer,
-        index_buffer: &wgpu::Buffer,
-        index_buffer_len: u32,
-    ) {
+    /// Re-create the off-screen rendering buffer if the new size doesn't equal to the current size.
+    pub fn resize(&mut self, device: &wgpu::Device, surface_size: (u32, u32)) {
         if surface_size != (self.frame.width(), self.frame.height()) {
             debug!("Re-creating the off-screen buffer to fit in the new size: {surface_size:?}");
             self.update_texture(device, surface_size);
         }
+    }
+
+    /// Pre-transform the content when rendering it to the surface, so that the compositor doesn't
+    /// need to rotate it for a rotated output. The surface must be resized as well.
+    pub fn set_transform(&self, queue: &wgpu::Queue, transform: Transform) {
+        let vertices = vertex::transformed_vertices(transform);
+        queue.write_buffer(&self.vertices, 0, bytemuck::cast_slice(&vertices));
+    }
 
+    /// Update the content of off-screen rendering buffer. `draws` are drawn in order, so the
+    /// padding should come before the wallpaper itself. Call `resize` first if the surface size
+    /// may have changed.
+    #[tracing::instrument(skip(self, encoder, draws))]
+    pub fn update_pass(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        default_color: (f64, f64, f64),
+        draws: &[Draw<'_>],
+    ) {
         debug!("Trying to render the off-screen buffer ...");
         let texture_view = self
             .frame
@@ -186,13 +192,13 @@ impl OffScreen {
             occlusion_query_set: None,
         });
 
-        render_pass.set_pipeline(render_pipeline);
-        for (i, &bind_group) in bind_groups.iter().enumerate() {
-            render_pass.set_bind_group(i as u32, bind_group, &[]);
+        for draw in draws {
+            render_pass.set_pipeline(draw.render_pipeline);
+            render_pass.set_bind_group(0, draw.bind_group, &[]);
+            render_pass.set_vertex_buffer(0, draw.vertex_buffer.slice(..));
+            render_pass.set_index_buffer(draw.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+            render_pass.draw_indexed(0..draw.index_count, 0, 0..1);
         }
-        render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
-        render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint16);
-        render_pass.draw_indexed(0..index_buffer_len, 0, 0..1);
     }
 
     /// Render to the surface. Just draw the whole off-screen buffer onto the entire surface.
@@ -259,3 +265,57 @@ impl OffScreen {
         wgpu::TextureFormat::Rgba8UnormSrgb
     }
 }
+
+/// Copy an off-screen frame back from the GPU, after the rendering submitted before is done. The
+/// pixels are sRGB encoded, as they are stored in the frame. The device is polled on a blocking
+/// thread, so that the event loop isn't blocked while waiting for the GPU.
+#[tracing::instrument(skip_all)]
+pub async fn read_frame(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    frame: &wgpu::Texture,
+) -> Result<RgbaImage> {
+    let (width, height) = (frame.width(), frame.height());
+    // Rows of the copied buffer must be aligned.
+    let bytes_per_row = (4 * width).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+
+    debug!("Copying the off-screen frame of {width}x{height} to a buffer ...");
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Frame read-back buffer"),
+        size: bytes_per_row as u64 * height as u64,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+    encoder.copy_texture_to_buffer(
+        frame.as_image_copy(),
+        wgpu::TexelCopyBufferInfo {
+            buffer: &buffer,
+            layout: wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(bytes_per_row),
+                rows_per_image: Some(height),
+            },
+        },
+        texture::texture_size(width, height),
+    );
+    queue.submit(Some(encoder.finish()));
+
+    debug!("Mapping the buffer ...");
+    let slice = buffer.slice(..);
+    let (tx, rx) = oneshot::channel();
+    slice.map_async(wgpu::MapMode::Read, move |result| {
+        let _ = tx.send(result);
+    });
+    let device = device.clone();
+    tokio::task::spawn_blocking(move || device.poll(wgpu::PollType::wait_indefinitely())).await??;
+    rx.await??;
+
+    let mut pixels = Vec::with_capacity(4 * width as usize * height as usize);
+    for row in slice.get_mapped_range().chunks(bytes_per_row as usize) {
+        pixels.extend_from_slice(&row[..4 * width as usize]);
+    }
+    buffer.unmap();
+
+    RgbaImage::from_raw(width, height, pixels).ok_or(anyhow!("The frame is incomplete"))
+}
@@ -0,0 +1,320 @@
+use crate::wallpaper::{
+    MAG_FILTER, MIN_FILTER, MIPMAP_FILTER, bind_group, off_screen::Draw, sampler,
+    shaders::gradient::GradientPass, texture, vertex,
+};
+use common::cli::{
+    client::{Fill, Gradient, GradientKind, GradientStop},
+    server::{Placement, ResizeOption, SourceTransform, TileOptions},
+};
+use image::RgbaImage;
+use tracing::{debug, warn};
+use wgpu::{self, util::DeviceExt};
+
+/// The blurred copy of an image is downscaled to this size on its longer side before blurring, so
+/// that blurring is cheap and upscaling it on the GPU blurs it even more.
+const BLUR_THUMBNAIL_SIZE: u32 = 64;
+const BLUR_SIGMA: f32 = 4.0;
+
+/// What is drawn onto the whole off-screen buffer before the image, when the image does not fill
+/// the screen. Filling with a single color doesn't need any padding pass, because the off-screen
+/// buffer is cleared with the fill color anyway.
+pub enum Padding {
+    Gradient(GradientPass),
+    Image(ImagePadding),
+}
+
+/// Copies of an image drawn in its paddings. They are expensive to make from large images, so they
+/// are made along with decoding the image, off the event loop.
+#[derive(Clone, Default)]
+pub struct PaddingImages {
+    /// Downscaled and blurred copy for `Fill::Blur`.
+    blurred: Option<RgbaImage>,
+    /// Copy of the crop rectangle for `Fill::Mirror` and `Fill::Extend`.
+    cropped: Option<RgbaImage>,
+}
+
+impl PaddingImages {
+    /// Make the copies of `img` needed to fill its paddings with any of `fills`.
+    pub fn new(img: &RgbaImage, fills: &[Fill], source_transform: SourceTransform) -> Self {
+        let blurred = fills.contains(&Fill::Blur).then(|| {
+            debug!("Creating blurred copy of the image ...");
+            Self::blur(img)
+        });
+
+        let mirrored = fills
+            .iter()
+            .any(|fill| matches!(fill, Fill::Mirror | Fill::Extend));
+        let cropped = source_transform.crop.filter(|_| mirrored).map(|crop| {
+            debug!("Creating cropped copy of the image ...");
+            let (x, y, w, h) = vertex::clamp_crop(crop, (img.width(), img.height()));
+            image::imageops::crop_imm(img, x, y, w, h).to_image()
+        });
+
+        Self { blurred, cropped }
+    }
+
+    fn blur(img: &RgbaImage) -> RgbaImage {
+        let (width, height) = (img.width().max(1), img.height().max(1));
+        let (width, height) = if width > height {
+            (
+                BLUR_THUMBNAIL_SIZE,
+                (BLUR_THUMBNAIL_SIZE * height / width).max(1),
+            )
+        } else {
+            (
+                (BLUR_THUMBNAIL_SIZE * width / height).max(1),
+                BLUR_THUMBNAIL_SIZE,
+            )
+        };
+
+        let thumbnail = image::imageops::thumbnail(img, width, height);
+        image::imageops::blur(&thumbnail, BLUR_SIGMA)
+    }
+}
+
+/// Padding drawn from the image itself, or from a blurred or cropped copy of it.
+pub struct ImagePadding {
+    fill: Fill,
+    source_transform: SourceTransform,
+    // Keep the copy alive, as it's only referenced by the bind group.
+    _texture: Option<wgpu::Texture>,
+    texture_size: (u32, u32),
+    /// The rectangle of the image copied for mirroring or extending it, so that the pixels cropped
+    /// away aren't drawn in the padding.
+    crop: Option<(u32, u32, u32, u32)>,
+    image_size: (u32, u32),
+    bind_group: wgpu::BindGroup,
+    vertex_buffer: wgpu::Buffer,
+}
+
+impl Padding {
+    /// Create the padding pass. Return `None` if the padding is simply cleared with the fill color.
+    #[allow(clippy::too_many_arguments)]
+    #[tracing::instrument(skip(device, queue, images, image_texture, image_vertices))]
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        fill: Fill,
+        fill_rgb: (u8, u8, u8),
+        images: &PaddingImages,
+        image_texture: &wgpu::Texture,
+        surface_size: (u32, u32),
+        target_format: wgpu::TextureFormat,
+        image_vertices: &[vertex::Vertex],
+        source_transform: SourceTransform,
+    ) -> Option<Self> {
+        let address_mode = match fill {
+            Fill::Color => return None,
+            Fill::Gradient { rgb_end, angle } => {
+                debug!("Creating gradient padding ...");
+                let gradient = Gradient {
+                    kind: GradientKind::Linear,
+                    stops: vec![
+                        GradientStop {
+                            rgb: fill_rgb,
+                            offset: None,
+                        },
+                        GradientStop {
+                            rgb: rgb_end,
+                            offset: None,
+                        },
+                    ],
+                    angle,
+                };
+
+                return Some(Self::Gradient(GradientPass::new(
+                    device,
+                    gradient,
+                    surface_size,
+                    target_format,
+                )));
+            }
+            Fill::Blur | Fill::Extend => wgpu::AddressMode::ClampToEdge,
+            Fill::Mirror => wgpu::AddressMode::MirrorRepeat,
+        };
+
+        let image_size = (image_texture.width(), image_texture.height());
+        let crop = match fill {
+            Fill::Blur => None,
+            _ => source_transform
+                .crop
+                .map(|crop| vertex::clamp_crop(crop, image_size)),
+        };
+
+        let copy = match fill {
+            Fill::Blur => Some((&images.blurred, "Blurred padding texture")),
+            _ => crop.map(|_| (&images.cropped, "Cropped padding texture")),
+        };
+        let (texture, texture_size) = match copy {
+            Some((Some(copy), label)) => {
+                let texture = Self::upload_texture(device, queue, copy, label);
+                (Some(texture), (copy.width(), copy.height()))
+            }
+            Some((None, label)) => {
+                warn!("{label} isn't prepared, filling the padding with the fill color");
+                return None;
+            }
+            None => (None, image_size),
+        };
+
+        let sampler = device.create_sampler(&sampler::desc(
+            Some("Padding sampler"),
+            (address_mode, address_mode),
+            MAG_FILTER,
+            if let Fill::Blur = fill {
+                wgpu::FilterMode::Linear
+            } else {
+                MIN_FILTER
+            },
+            MIPMAP_FILTER,
+        ));
+
+        let texture_view = texture
+            .as_ref()
+            .unwrap_or(image_texture)
+            .create_view(&texture::image_view_desc(Some("Padding texture view")));
+        let layout = device.create_bind_group_layout(
+            &bind_group::texture_and_sampler::layout_desc(Some("Padding bind group layout")),
+        );
+        let bind_group = bind_group::texture_and_sampler::bind_group(
+            device,
+            Some("Padding bind group"),
+            &layout,
+            &texture_view,
+            &sampler,
+        );
+
+        let vertices = Self::image_padding_vertices(
+            fill,
+            source_transform,
+            surface_size,
+            texture_size,
+            crop,
+            image_size,
+            image_vertices,
+        );
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Padding vertex buffer"),
+            contents: bytemuck::cast_slice(&vertices),
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        });
+
+        Some(Self::Image(ImagePadding {
+            fill,
+            source_transform,
+            _texture: texture,
+            texture_size,
+            crop,
+            image_size,
+            bind_group,
+            vertex_buffer,
+        }))
+    }
+
+    /// The padding depends on the surface size and where the image is drawn. Call this method
+    /// whenever either of them changes.
+    pub fn resize(
+        &self,
+        queue: &wgpu::Queue,
+        surface_size: (u32, u32),
+        image_vertices: &[vertex::Vertex],
+    ) {
+        match self {
+            Self::Gradient(gradient) => gradient.resize(queue, surface_size),
+            Self::Image(padding) => {
+                let vertices = Self::image_padding_vertices(
+                    padding.fill,
+                    padding.source_transform,
+                    surface_size,
+                    padding.texture_size,
+                    padding.crop,
+                    padding.image_size,
+                    image_vertices,
+                );
+                queue.write_buffer(&padding.vertex_buffer, 0, bytemuck::cast_slice(&vertices));
+            }
+        }
+    }
+
+    /// Image paddings are drawn with the same pipeline as the image.
+    pub fn draw<'a>(
+        &'a self,
+        image_pipeline: &'a wgpu::RenderPipeline,
+        index_buffer: &'a wgpu::Buffer,
+    ) -> Draw<'a> {
+        match self {
+            Self::Gradient(gradient) => gradient.draw(index_buffer),
+            Self::Image(padding) => Draw {
+                render_pipeline: image_pipeline,
+                bind_group: &padding.bind_group,
+                vertex_buffer: &padding.vertex_buffer,
+                index_buffer,
+                index_count: vertex::NUM_INDEX,
+            },
+        }
+    }
+
+    fn image_padding_vertices(
+        fill: Fill,
+        source_transform: SourceTransform,
+        surface_size: (u32, u32),
+        texture_size: (u32, u32),
+        crop: Option<(u32, u32, u32, u32)>,
+        image_size: (u32, u32),
+        image_vertices: &[vertex::Vertex],
+    ) -> Vec<vertex::Vertex> {
+        match fill {
+            // Zoom the blurred copy in to cover the whole screen. It is a downscaled copy of the
+            // whole image, so it can only be oriented like the image, but not cropped.
+            Fill::Blur => vertex::create_vertex_buffer_with_resize_option(
+                surface_size,
+                texture_size,
+                ResizeOption::Crop,
+                TileOptions::default(),
+                Placement::default(),
+                SourceTransform {
+                    crop: None,
+                    ..source_transform
+                },
+            ),
+            // The cropped copy is mirrored or clamped at the edges of the crop rectangle.
+            _ => match crop {
+                Some(crop) => vertex::extend_to_surface(&vertex::to_crop_coords(
+                    image_vertices,
+                    crop,
+                    image_size,
+                )),
+                None => vertex::extend_to_surface(image_vertices),
+            },
+        }
+    }
+
+    fn upload_texture(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        img: &RgbaImage,
+        label: &str,
+    ) -> wgpu::Texture {
+        let (width, height) = (img.width(), img.height());
+        let size = texture::texture_size(width, height);
+        let desc = texture::image_srgb_unorm_desc(Some(label), size, 1);
+        let texture = device.create_texture(&desc);
+        queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            img,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * width),
+                rows_per_image: Some(height),
+            },
+            size,
+        );
+
+        texture
+    }
+}
@@ -0,0 +1,172 @@
+use tracing::{debug, warn};
+use wayland_client::{
+    Connection, Dispatch, QueueHandle, globals::GlobalList, protocol::wl_surface::WlSurface,
+};
+use wayland_protocols::wp::{
+    fractional_scale::v1::client::{
+        wp_fractional_scale_manager_v1::WpFractionalScaleManagerV1,
+        wp_fractional_scale_v1::{self, WpFractionalScaleV1},
+    },
+    viewporter::client::{wp_viewport::WpViewport, wp_viewporter::WpViewporter},
+};
+
+use crate::wallpaper::Wallpaper;
+
+/// `wp_fractional_scale_v1` sends the scale as a fraction with this denominator.
+const FRACTIONAL_SCALE_DENOMINATOR: f64 = 120.0;
+
+/// Globals needed for fractional scaling. Either of them can be missing if the compositor doesn't
+/// support it, and we fall back to integer buffer scale then.
+pub struct ScaleState {
+    fractional_scale_manager: Option<WpFractionalScaleManagerV1>,
+    viewporter: Option<WpViewporter>,
+}
+
+impl ScaleState {
+    pub fn bind(globals: &GlobalList, qh: &QueueHandle<Wallpaper>) -> Self {
+        let fractional_scale_manager = globals
+            .bind(qh, 1..=1, ())
+            .inspect_err(|e| debug!("Fractional scale is not supported: {e}"))
+            .ok();
+        let viewporter = globals
+            .bind(qh, 1..=1, ())
+            .inspect_err(|e| debug!("Viewporter is not supported: {e}"))
+            .ok();
+
+        Self {
+            fractional_scale_manager,
+            viewporter,
+        }
+    }
+
+    /// Track the scale of the surface. The fractional scale is used only if both protocols are
+    /// supported, because the buffer must be scaled down by the viewport.
+    pub fn surface_scale(&self, qh: &QueueHandle<Wallpaper>, surface: &WlSurface) -> SurfaceScale {
+        let (fractional_scale, viewport) = match (&self.fractional_scale_manager, &self.viewporter)
+        {
+            (Some(manager), Some(viewporter)) => (
+                Some(manager.get_fractional_scale(surface, qh, surface.clone())),
+                Some(viewporter.get_viewport(surface, qh, ())),
+            ),
+            _ => (None, None),
+        };
+
+        SurfaceScale {
+            buffer_scale: 1,
+            preferred_scale: None,
+            _fractional_scale: fractional_scale,
+            viewport,
+        }
+    }
+}
+
+/// Scale of a surface, which decides the physical size of its buffer.
+pub struct SurfaceScale {
+    /// Integer scale, used until the compositor sends a fractional one.
+    buffer_scale: i32,
+    /// Fractional scale in 120ths sent by the compositor.
+    preferred_scale: Option<u32>,
+    // Keep the fractional scale object alive, or we will never receive the preferred scale.
+    _fractional_scale: Option<WpFractionalScaleV1>,
+    viewport: Option<WpViewport>,
+}
+
+impl SurfaceScale {
+    /// Return `true` if the scale is changed.
+    pub fn set_buffer_scale(&mut self, factor: i32) -> bool {
+        let changed = self.buffer_scale != factor;
+        self.buffer_scale = factor;
+        changed && self.preferred_scale.is_none()
+    }
+
+    /// Return `true` if the scale is changed.
+    pub fn set_preferred_scale(&mut self, scale: u32) -> bool {
+        self.preferred_scale.replace(scale) != Some(scale)
+    }
+
+    pub fn factor(&self) -> f64 {
+        match self.preferred_scale {
+            Some(scale) if self.viewport.is_some() => scale as f64 / FRACTIONAL_SCALE_DENOMINATOR,
+            _ => self.buffer_scale as f64,
+        }
+    }
+
+    /// The size of the buffer in physical pixels.
+    pub fn physical_size(&self, (width, height): (u32, u32)) -> (u32, u32) {
+        let factor = self.factor();
+        (
+            ((width as f64 * factor).round() as u32).max(1),
+            ((height as f64 * factor).round() as u32).max(1),
+        )
+    }
+
+    /// Tell the compositor how the buffer maps to the surface of `logical_size`. This state is
+    /// applied on next commit.
+    pub fn apply(&self, surface: &WlSurface, (width, height): (u32, u32)) {
+        match &self.viewport {
+            Some(viewport) => {
+                surface.set_buffer_scale(1);
+                viewport.set_destination(width as i32, height as i32);
+            }
+            None => surface.set_buffer_scale(self.buffer_scale),
+        }
+    }
+}
+
+impl Dispatch<WpFractionalScaleManagerV1, ()> for Wallpaper {
+    fn event(
+        _state: &mut Self,
+        _proxy: &WpFractionalScaleManagerV1,
+        _event: <WpFractionalScaleManagerV1 as wayland_client::Proxy>::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        // No events.
+    }
+}
+
+impl Dispatch<WpFractionalScaleV1, WlSurface> for Wallpaper {
+    fn event(
+        state: &mut Self,
+        _proxy: &WpFractionalScaleV1,
+        event: wp_fractional_scale_v1::Event,
+        surface: &WlSurface,
+        conn: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        match event {
+            wp_fractional_scale_v1::Event::PreferredScale { scale } => {
+                debug!("`preferred_scale` received: {scale}/120");
+                state.preferred_scale_changed(conn, qh, surface, scale);
+            }
+            _ => warn!("Unknown fractional scale event"),
+        }
+    }
+}
+
+impl Dispatch<WpViewporter, ()> for Wallpaper {
+    fn event(
+        _state: &mut Self,
+        _proxy: &WpViewporter,
+        _event: <WpViewporter as wayland_client::Proxy>::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        // No events.
+    }
+}
+
+impl Dispatch<WpViewport, ()> for Wallpaper {
+    fn event(
+        _state: &mut Self,
+        _proxy: &WpViewport,
+        _event: <WpViewport as wayland_client::Proxy>::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        // No events.
+    }
+}
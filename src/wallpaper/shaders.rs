@@ -1,3 +1,4 @@
+pub mod gradient;
 pub mod transition;
 pub mod wallpaper;
 
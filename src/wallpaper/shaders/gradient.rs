@@ -0,0 +1,171 @@
+use crate::wallpaper::{bind_group, off_screen::Draw, render_pipeline, shaders, vertex};
+use common::cli::client::{Gradient, GradientKind, MAX_GRADIENT_STOPS};
+use wgpu::{self, util::DeviceExt};
+
+/// Must be the same as `MAX_STOPS` in `gradient.wgsl`.
+pub const MAX_STOPS: usize = MAX_GRADIENT_STOPS;
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct GradientUniform {
+    kind: u32,
+    stop_count: u32,
+    angle: f32,
+    aspect: f32,
+    stops: [[f32; 4]; MAX_STOPS],
+}
+
+impl GradientUniform {
+    fn new(gradient: &Gradient, (width, height): (u32, u32)) -> Self {
+        let kind = match gradient.kind {
+            GradientKind::Linear => 0,
+            GradientKind::Radial => 1,
+        };
+
+        let mut stops = [[0.0; 4]; MAX_STOPS];
+        let offsets = resolve_offsets(gradient);
+        for ((stop, offset), uniform) in gradient.stops.iter().zip(offsets).zip(stops.iter_mut()) {
+            let (r, g, b) = stop.rgb;
+            *uniform = [
+                srgb_to_linear(r),
+                srgb_to_linear(g),
+                srgb_to_linear(b),
+                offset,
+            ];
+        }
+
+        Self {
+            kind,
+            stop_count: gradient.stops.len().min(MAX_STOPS) as u32,
+            angle: gradient.angle as f32,
+            aspect: width.max(1) as f32 / height.max(1) as f32,
+            stops,
+        }
+    }
+}
+
+/// Fill in missing offsets by spreading those stops evenly between their neighbours, and make
+/// sure that offsets never decrease, like CSS does.
+fn resolve_offsets(gradient: &Gradient) -> Vec<f32> {
+    let stops = &gradient.stops[..gradient.stops.len().min(MAX_STOPS)];
+    let mut offsets: Vec<Option<f32>> = stops.iter().map(|s| s.offset.map(|o| o as f32)).collect();
+
+    if let Some(first) = offsets.first_mut() {
+        first.get_or_insert(0.0);
+    }
+    if let Some(last) = offsets.last_mut() {
+        last.get_or_insert(1.0);
+    }
+
+    let mut last_known = 0;
+    for i in 1..offsets.len() {
+        let Some(offset) = offsets[i] else {
+            continue;
+        };
+
+        let from = offsets[last_known].unwrap_or(0.0);
+        let steps = (i - last_known) as f32;
+        for (step, missing) in offsets[last_known + 1..i].iter_mut().enumerate() {
+            *missing = Some(from + (offset - from) * (step + 1) as f32 / steps);
+        }
+        last_known = i;
+    }
+
+    let mut max = 0.0f32;
+    offsets
+        .into_iter()
+        .map(|offset| {
+            max = max.max(offset.unwrap_or(max));
+            max
+        })
+        .collect()
+}
+
+fn srgb_to_linear(c: u8) -> f32 {
+    let c = c as f32 / 255.0;
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Procedurally render a gradient, or a solid color which is a gradient with only one stop, onto
+/// the whole target.
+pub struct GradientPass {
+    gradient: Gradient,
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    render_pipeline: wgpu::RenderPipeline,
+    vertex_buffer: wgpu::Buffer,
+}
+
+impl GradientPass {
+    pub fn new(
+        device: &wgpu::Device,
+        gradient: Gradient,
+        surface_size: (u32, u32),
+        target_format: wgpu::TextureFormat,
+    ) -> Self {
+        let uniform_buffer = bind_group::uniform::create_buffer(
+            device,
+            Some("Gradient data"),
+            GradientUniform::new(&gradient, surface_size),
+        );
+        let layout = device.create_bind_group_layout(&bind_group::uniform::layout_desc(
+            Some("Gradient data bind group layout"),
+            &bind_group::uniform::fragment_uniforms_layout_entries(1),
+        ));
+        let bind_group = bind_group::uniform::uniforms_bind_group(
+            device,
+            Some("Gradient data bind group"),
+            &layout,
+            &[&uniform_buffer],
+        );
+
+        let vertex_buffer = vertex::STRETCH_VERTICES;
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Gradient vertex buffer"),
+            contents: bytemuck::cast_slice(vertex_buffer),
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::VERTEX,
+        });
+
+        let shader = device.create_shader_module(wgpu::include_wgsl!("gradient.wgsl"));
+        let render_pipeline = render_pipeline::create_pipeline(
+            device,
+            Some("Gradient pipeline layout"),
+            Some("Gradient pipeline"),
+            &[&layout],
+            &shader,
+            Some("vs_main"),
+            shaders::wallpaper::BUFFERS,
+            Some("fs_main"),
+            &shaders::target(target_format),
+        );
+
+        Self {
+            gradient,
+            uniform_buffer,
+            bind_group,
+            render_pipeline,
+            vertex_buffer,
+        }
+    }
+
+    /// The gradient depends on the aspect ratio of the surface. Call this method whenever the
+    /// surface is resized.
+    pub fn resize(&self, queue: &wgpu::Queue, surface_size: (u32, u32)) {
+        let data = GradientUniform::new(&self.gradient, surface_size);
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[data]));
+    }
+
+    pub fn draw<'a>(&'a self, index_buffer: &'a wgpu::Buffer) -> Draw<'a> {
+        Draw {
+            render_pipeline: &self.render_pipeline,
+            bind_group: &self.bind_group,
+            vertex_buffer: &self.vertex_buffer,
+            index_buffer,
+            index_count: vertex::NUM_INDEX,
+        }
+    }
+}
@@ -0,0 +1,70 @@
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) texture_coords: vec2<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) ndc_position: vec3<f32>,
+}
+
+@vertex
+fn vs_main(
+    model: VertexInput
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(model.position, 1.0);
+    out.ndc_position = model.position;
+    return out;
+}
+
+const KIND_LINEAR: u32 = 0u;
+const KIND_RADIAL: u32 = 1u;
+const MAX_STOPS: u32 = 16u;
+
+struct Gradient {
+    kind: u32,
+    stop_count: u32,
+    // In degrees, counter-clockwise from left to right.
+    angle: f32,
+    // Width divided by height of the surface.
+    aspect: f32,
+    // Linear RGB in `xyz` and offset in `w`, sorted by offsets.
+    stops: array<vec4<f32>, MAX_STOPS>,
+}
+
+@group(0) @binding(0)
+var<uniform> gradient: Gradient;
+
+@fragment
+fn fs_main(
+    in: VertexOutput
+) -> @location(0) vec4<f32> {
+    // Scale x so that angles and circles are not distorted by the aspect ratio.
+    let position = vec2(in.ndc_position[0] * gradient.aspect, in.ndc_position[1]);
+
+    var t = 0.0;
+    if gradient.kind == KIND_RADIAL {
+        // Reach the last stop at the corners of the screen.
+        t = length(position) / length(vec2(gradient.aspect, 1.0));
+    } else {
+        // Reach the first and the last stop at the farthest points along the direction.
+        let angle = radians(gradient.angle);
+        let d = vec2(cos(angle), sin(angle));
+        let extent = abs(d[0]) * gradient.aspect + abs(d[1]);
+        t = (dot(position, d) / extent + 1.0) / 2.0;
+    }
+    t = clamp(t, 0.0, 1.0);
+
+    var color = gradient.stops[0].xyz;
+    for (var i = 1u; i < min(gradient.stop_count, MAX_STOPS); i++) {
+        let prev = gradient.stops[i - 1u];
+        let next = gradient.stops[i];
+        if t >= prev.w {
+            let f = clamp((t - prev.w) / max(next.w - prev.w, 1e-6), 0.0, 1.0);
+            color = mix(prev.xyz, next.xyz, f);
+        }
+    }
+
+    return vec4<f32>(color, 1.0);
+}
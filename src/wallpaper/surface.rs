@@ -0,0 +1,811 @@
+use anyhow::{Result, anyhow};
+use common::{
+    cli::{
+        client::{EaseKind, TransitionKind, TransitionOptions},
+        server as server_cli,
+    },
+    utils,
+};
+use image::RgbaImage;
+use smithay_client_toolkit::{
+    compositor::CompositorState,
+    shell::{
+        WaylandSurface,
+        wlr_layer::{Anchor, KeyboardInteractivity, Layer, LayerShell, LayerSurface},
+    },
+};
+use std::sync::Arc;
+use tracing::{debug, error, warn};
+use wayland_client::{
+    Connection, QueueHandle,
+    protocol::{wl_output::Transform, wl_output::WlOutput, wl_surface::WlSurface},
+};
+use wgpu::{self, util::DeviceExt};
+
+use crate::{
+    ease,
+    server::TaskHandle,
+    wallpaper::{
+        Content, DecodedImage, Gpu, Wallpaper, bind_group, config,
+        config::Configurable,
+        misc,
+        off_screen::{self, Draw, OffScreen},
+        padding::{Padding, PaddingImages},
+        sampler,
+        scale::{ScaleState, SurfaceScale},
+        shaders::{self, gradient::GradientPass},
+        texture,
+        transition_state::{TransitionRenderError, TransitionState},
+        vertex::{self, NUM_INDEX},
+    },
+};
+
+/// Where the compositor places the layer surfaces.
+#[derive(Clone, Copy, Debug)]
+pub struct LayerOptions {
+    layer: Layer,
+    anchor: Anchor,
+    /// Top, right, bottom and left margins.
+    margin: (i32, i32, i32, i32),
+    size: (u32, u32),
+    exclusive_zone: i32,
+}
+
+impl Default for LayerOptions {
+    fn default() -> Self {
+        Self {
+            layer: Layer::Background,
+            anchor: Anchor::all(),
+            margin: (0, 0, 0, 0),
+            // Ask the compositor to decide the size.
+            size: (0, 0),
+            // Ask the compositors not to move this surface to accommodate for other surfaces, and
+            // to extend this surface all the way to the edges it anchored.
+            exclusive_zone: -1,
+        }
+    }
+}
+
+impl LayerOptions {
+    pub fn from_cli(options: &server_cli::SurfaceOptions) -> Result<Self> {
+        let default = Self::default();
+        let layer = match options.layer.unwrap_or_default() {
+            server_cli::SurfaceLayer::Background => Layer::Background,
+            server_cli::SurfaceLayer::Bottom => Layer::Bottom,
+        };
+        let anchor = match &options.anchor {
+            Some(edges) => edges.iter().fold(Anchor::empty(), |anchor, edge| {
+                anchor
+                    | match edge {
+                        server_cli::Edge::Top => Anchor::TOP,
+                        server_cli::Edge::Bottom => Anchor::BOTTOM,
+                        server_cli::Edge::Left => Anchor::LEFT,
+                        server_cli::Edge::Right => Anchor::RIGHT,
+                    }
+            }),
+            None => default.anchor,
+        };
+        let size = options.size.unwrap_or(default.size);
+
+        // The compositor can only decide the size along an axis stretched between two edges.
+        if size.0 == 0 && !anchor.contains(Anchor::LEFT | Anchor::RIGHT) {
+            return Err(anyhow!(
+                "The width must be given unless the wallpaper is anchored to both left and right edges"
+            ));
+        }
+        if size.1 == 0 && !anchor.contains(Anchor::TOP | Anchor::BOTTOM) {
+            return Err(anyhow!(
+                "The height must be given unless the wallpaper is anchored to both top and bottom edges"
+            ));
+        }
+
+        Ok(Self {
+            layer,
+            anchor,
+            margin: options.margin.unwrap_or(default.margin),
+            size,
+            exclusive_zone: options.exclusive_zone.unwrap_or(default.exclusive_zone),
+        })
+    }
+}
+
+/// The layer surface covering one output, and everything needed to render the wallpaper onto it.
+pub struct OutputSurface {
+    output: WlOutput,
+    /// Name of the output, e.g. `DP-1`, used to remember its wallpaper after it is unplugged.
+    name: Option<String>,
+    layer_surface: LayerSurface,
+
+    // States
+    /// Whether the surface finished the first configuration. If this flag is false, we cannot
+    /// render to the surface and commit. Once `LayerShellHandler::configure` is called, this flag
+    /// will be set to true.
+    first_configured: bool,
+    /// Whether we have something new to be drawed. `draw` method will render to the surface and
+    /// commit if both `first_configured` and `damaged` are true. This flag will be set to true
+    /// when:
+    ///
+    /// 1. A `new_size` is received from the `LayerShellHandler::configure` method.
+    /// 2. A new image is received by the daemon from the client.
+    /// 3. The daemon is doing transition work between two images.
+    damaged: bool,
+    /// The size of the surface in the compositor's logical coordinates, received from
+    /// `LayerShellHandler::configure`.
+    logical_size: (u32, u32),
+    /// The physical size of the surface, which we actually render at, before the output transform
+    /// is applied.
+    size: (u32, u32),
+    /// Decides the physical size of the surface.
+    scale: SurfaceScale,
+    /// The content is pre-transformed with the output transform, so the compositor can use the
+    /// buffer directly on a rotated output.
+    transform: Transform,
+
+    /// Off-screen buffer
+    off_screen_buffer: OffScreen,
+
+    wgpu_surface: wgpu::Surface<'static>,
+    config: wgpu::SurfaceConfiguration,
+
+    content: Content,
+    image_texture: wgpu::Texture,
+    texture_width: u32,
+    texture_height: u32,
+    vertex_buffer: wgpu::Buffer,
+    /// Drawn before the image to fill the padding around it. `None` if the padding is filled with
+    /// the fill color, or if a solid color or a gradient is displayed.
+    padding: Option<Padding>,
+    /// Recreated along with the image, because its address mode depends on the resize option.
+    sampler: wgpu::Sampler,
+    bind_group: wgpu::BindGroup,
+    /// Procedurally rendered content, i.e. a solid color or a gradient. If set, it is drawn
+    /// instead of the image texture.
+    gradient: Option<GradientPass>,
+
+    // Transition state manager
+    transition: Option<TransitionState>,
+    /// Multiplies the speed of transitions, e.g. `0.1` for slow motion.
+    transition_speed: f64,
+}
+
+impl OutputSurface {
+    /// Create a layer surface on the output, which displays `content` decoded as `img`.
+    #[allow(clippy::too_many_arguments)]
+    #[tracing::instrument(skip_all, fields(name = ?name))]
+    pub fn new(
+        gpu: &Gpu,
+        conn: &Connection,
+        qh: &QueueHandle<Wallpaper>,
+        compositor_state: &CompositorState,
+        layer_shell_state: &LayerShell,
+        scale_state: &ScaleState,
+        layer_options: LayerOptions,
+        output: WlOutput,
+        name: Option<String>,
+        namespace: Option<String>,
+        content: Content,
+        img: &DecodedImage,
+    ) -> Result<Self> {
+        debug!("Trying to create a wayland layer surface");
+        let orig_surface = compositor_state.create_surface(qh);
+        let layer_surface = layer_shell_state.create_layer_surface(
+            qh,
+            orig_surface,
+            layer_options.layer,
+            namespace,
+            Some(&output),
+        );
+
+        debug!("Configuring the layer surface: {layer_options:?}");
+        let (width, height) = layer_options.size;
+        layer_surface.set_size(width, height);
+        layer_surface.set_anchor(layer_options.anchor);
+        let (top, right, bottom, left) = layer_options.margin;
+        layer_surface.set_margin(top, right, bottom, left);
+        layer_surface.set_exclusive_zone(layer_options.exclusive_zone);
+        layer_surface.set_keyboard_interactivity(KeyboardInteractivity::None);
+
+        debug!("Trying to track the scale of the surface ...");
+        let scale = scale_state.surface_scale(qh, layer_surface.wl_surface());
+
+        // Do not forget to commit the surface, or we will never receive the first `configure`
+        // event.
+        layer_surface.commit();
+
+        debug!("Trying to create a wgpu surface");
+        let wgpu_surface =
+            misc::layer_surface_to_wgpu_surface(conn, &layer_surface, &gpu.instance)?;
+
+        debug!("Trying to create a surface configuration ...");
+        let surface_caps = wgpu_surface.get_capabilities(&gpu.adapter);
+        let surface_format = surface_caps
+            .formats
+            .iter()
+            .find(|f| f.is_srgb())
+            .copied()
+            .unwrap_or(surface_caps.formats[0]);
+        let config = config::create(&surface_format, &surface_caps);
+
+        // HACK: As we don't know the surface size for now, use `stretch` to create the vertex buffer.
+        debug!("Trying to create the vertex buffer ...");
+        let vertex_buffer = vertex::STRETCH_VERTICES;
+        let vertex_buffer = gpu
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Vertex buffer"),
+                contents: bytemuck::cast_slice(vertex_buffer),
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            });
+
+        let (image_texture, sampler, bind_group) = Self::upload_image(gpu, &content, &img.rgba);
+
+        debug!("Creating off-screen buffer ...");
+        // HACK: As we don't know the surface size for now, use `1920x1080` to create the
+        // off-screen buffer.
+        let off_screen_buffer = OffScreen::create(&gpu.device, (1920, 1080), config.format);
+
+        let mut surface = Self {
+            output,
+            name,
+            layer_surface,
+            first_configured: false,
+            damaged: true,
+            logical_size: (1, 1),
+            size: (1, 1),
+            scale,
+            transform: Transform::Normal,
+            off_screen_buffer,
+            wgpu_surface,
+            config,
+            content: content.clone(),
+            texture_width: image_texture.width(),
+            texture_height: image_texture.height(),
+            image_texture,
+            vertex_buffer,
+            padding: None,
+            sampler,
+            bind_group,
+            gradient: None,
+            transition: None,
+            transition_speed: 1.0,
+        };
+        surface.prepare_content(gpu, content, &img.paddings);
+
+        debug!("Output surface built!");
+        Ok(surface)
+    }
+
+    pub fn output(&self) -> &WlOutput {
+        &self.output
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn layer_surface(&self) -> &LayerSurface {
+        &self.layer_surface
+    }
+
+    pub fn wl_surface(&self) -> &WlSurface {
+        self.layer_surface.wl_surface()
+    }
+
+    pub fn content(&self) -> &Content {
+        &self.content
+    }
+
+    pub fn scale_factor(&self) -> f64 {
+        self.scale.factor()
+    }
+
+    /// Read back the frame last drawn, i.e. the wallpaper after resizing and padding, or the
+    /// current frame of the running transition. It isn't rotated by the output transform.
+    pub async fn screenshot(&self, gpu: &Gpu) -> Result<RgbaImage> {
+        if !self.first_configured {
+            return Err(anyhow!("The surface isn't configured yet"));
+        }
+
+        let frame = self
+            .transition
+            .as_ref()
+            .and_then(TransitionState::current_frame)
+            .unwrap_or(self.off_screen_buffer.current_frame());
+        off_screen::read_frame(&gpu.device, &gpu.queue, frame).await
+    }
+
+    /// Draw the next frame. Return whether the transition is finished by this frame.
+    #[tracing::instrument(skip_all, fields(name = ?self.name))]
+    pub fn draw(&mut self, gpu: &Gpu, qh: &QueueHandle<Wallpaper>) -> bool {
+        if !self.first_configured {
+            warn!("The surface hasn't be configured yet. Stop drawing ...");
+            return false;
+        }
+
+        if !self.damaged {
+            debug!("The surface has nothing new to draw. Stop drawing ...");
+            return false;
+        }
+        self.damaged = false;
+
+        let output = match self.wgpu_surface.get_current_texture() {
+            Ok(output) => output,
+            Err(e) => {
+                error!("Cannot get the current texture of the surface! : {e}");
+                return false;
+            }
+        };
+        let view = output
+            .texture
+            .create_view(&texture::surface_view_desc(Some("Surface texture view")));
+
+        if let Some(mut transition_state) = self.transition.take() {
+            // If we have some animating transition, hijack the normal rendering progress.
+            let mut encoder = gpu
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+
+            let now = std::time::Instant::now();
+            let finished = match transition_state.render_pass(
+                &gpu.device,
+                &mut encoder,
+                now,
+                &view,
+                self.content.fill_rgb,
+            ) {
+                Ok(_) => false,
+                Err(e) => match e {
+                    TransitionRenderError::SameFrame => false,
+                    TransitionRenderError::Finished => true,
+                },
+            };
+
+            // A paused transition doesn't need new frames until it's resumed or moved.
+            let paused = transition_state.is_paused();
+            if !finished {
+                // Continue the transition in the next frame.
+                self.transition = Some(transition_state);
+            } else {
+                // Or the next frame will just show the final image.
+                self.off_screen_buffer
+                    .render_pass(&mut encoder, &view, self.content.fill_rgb);
+            }
+
+            if finished || !paused {
+                self.damaged = true;
+                let surface = self.wl_surface().clone();
+                self.wl_surface().frame(qh, surface);
+            }
+
+            debug!("Damaging the whole surface ...");
+            let width = self.config.width as i32;
+            let height = self.config.height as i32;
+            self.wl_surface().damage_buffer(0, 0, width, height);
+            gpu.queue.submit(std::iter::once(encoder.finish()));
+
+            debug!("Submitted a transition frame!");
+            output.present();
+            return finished;
+        }
+
+        debug!("Normal rendering. Damaging the whole surface ...");
+        let width = self.config.width as i32;
+        let height = self.config.height as i32;
+        self.wl_surface().damage_buffer(0, 0, width, height);
+
+        let mut encoder = gpu
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        {
+            self.off_screen_buffer.resize(&gpu.device, self.size);
+            self.off_screen_buffer.update_pass(
+                &mut encoder,
+                self.content.fill_rgb,
+                &self.draws(gpu),
+            );
+            self.off_screen_buffer
+                .render_pass(&mut encoder, &view, self.content.fill_rgb);
+        }
+
+        gpu.queue.submit(std::iter::once(encoder.finish()));
+        output.present();
+        false
+    }
+
+    pub fn configure(&mut self, gpu: &Gpu, qh: &QueueHandle<Wallpaper>, new_size: (u32, u32)) {
+        self.logical_size = new_size;
+        self.first_configured = true;
+        self.resize(gpu, qh);
+    }
+
+    /// Resize the wgpu surface, and everything depending on its size, to the physical size of the
+    /// surface. Call this method whenever the logical size or the scale changes.
+    #[tracing::instrument(skip_all, fields(name = ?self.name))]
+    fn resize(&mut self, gpu: &Gpu, qh: &QueueHandle<Wallpaper>) {
+        self.size = self.scale.physical_size(self.logical_size);
+        debug!(
+            "Physical size: {:?}, scale: {}, transform: {:?}",
+            self.size,
+            self.scale.factor(),
+            self.transform
+        );
+        self.scale
+            .apply(self.layer_surface.wl_surface(), self.logical_size);
+
+        let (width, height) = self.size;
+        let buffer_size = if vertex::swaps_axes(self.transform) {
+            (height, width)
+        } else {
+            (width, height)
+        };
+        self.wl_surface().set_buffer_transform(self.transform);
+        self.off_screen_buffer
+            .set_transform(&gpu.queue, self.transform);
+        if let Some(transition) = &self.transition {
+            transition.set_transform(&gpu.queue, self.transform);
+        }
+        self.config.with_new_size(buffer_size);
+        self.wgpu_surface.configure(&gpu.device, &self.config);
+
+        // HACK: This the only way I know to get the surface size. Write to vertex buffer here.
+        debug!("Writing into vertex buffer ...");
+        let vertex_buffer = self.image_vertices();
+        gpu.queue
+            .write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&vertex_buffer));
+        if let Some(gradient) = &self.gradient {
+            gradient.resize(&gpu.queue, self.size);
+        }
+        if let Some(padding) = &self.padding {
+            padding.resize(&gpu.queue, self.size, &vertex_buffer);
+        }
+
+        self.damaged = true;
+        self.draw(gpu, qh);
+    }
+
+    pub fn set_buffer_scale(&mut self, gpu: &Gpu, qh: &QueueHandle<Wallpaper>, factor: i32) {
+        if self.scale.set_buffer_scale(factor) && self.first_configured {
+            self.resize(gpu, qh);
+        }
+    }
+
+    pub fn set_preferred_scale(&mut self, gpu: &Gpu, qh: &QueueHandle<Wallpaper>, scale: u32) {
+        if self.scale.set_preferred_scale(scale) && self.first_configured {
+            self.resize(gpu, qh);
+        }
+    }
+
+    pub fn set_transform(&mut self, gpu: &Gpu, qh: &QueueHandle<Wallpaper>, transform: Transform) {
+        if self.transform != transform {
+            self.transform = transform;
+            if self.first_configured {
+                self.resize(gpu, qh);
+            }
+        }
+    }
+
+    /// Display the new content, which is already decoded as `img`, and request a new frame.
+    #[tracing::instrument(skip_all, fields(name = ?self.name))]
+    pub fn change_image_and_request_frame(
+        &mut self,
+        gpu: &Gpu,
+        qh: &QueueHandle<Wallpaper>,
+        content: Content,
+        img: &DecodedImage,
+    ) {
+        self.set_content(gpu, content, img);
+
+        // Request a new frame to draw the new wallpaper.
+        self.request_frame(qh);
+    }
+
+    fn request_frame(&mut self, qh: &QueueHandle<Wallpaper>) {
+        self.damaged = true;
+        let wl_surface = self.wl_surface().clone();
+        self.wl_surface().frame(qh, wl_surface);
+        self.layer_surface.commit();
+    }
+
+    pub fn has_transition(&self) -> bool {
+        self.transition.is_some()
+    }
+
+    /// Jump to the final frame of the running transition. Return whether one was running.
+    pub fn cancel_transition(&mut self, qh: &QueueHandle<Wallpaper>) -> bool {
+        if self.transition.take().is_none() {
+            return false;
+        }
+
+        // A paused transition doesn't request frames by itself.
+        self.request_frame(qh);
+        true
+    }
+
+    pub fn pause_transition(&mut self) {
+        if let Some(transition) = &mut self.transition {
+            transition.pause(std::time::Instant::now());
+        }
+    }
+
+    pub fn resume_transition(&mut self, qh: &QueueHandle<Wallpaper>) {
+        if let Some(transition) = &mut self.transition
+            && transition.is_paused()
+        {
+            transition.resume(std::time::Instant::now());
+            self.request_frame(qh);
+        }
+    }
+
+    /// Move the running transition to `progress` within `[0, 1]`.
+    pub fn seek_transition(&mut self, qh: &QueueHandle<Wallpaper>, progress: f64) {
+        if let Some(transition) = &mut self.transition {
+            transition.seek(std::time::Instant::now(), progress);
+            if transition.is_paused() {
+                self.request_frame(qh);
+            }
+        }
+    }
+
+    /// Run the running transition and the later ones at `speed` times the real speed.
+    pub fn set_transition_speed(&mut self, speed: f64) {
+        self.transition_speed = speed;
+        if let Some(transition) = &mut self.transition {
+            transition.set_speed(std::time::Instant::now(), speed);
+        }
+    }
+
+    fn set_content(&mut self, gpu: &Gpu, content: Content, img: &DecodedImage) {
+        // Set the new texture, and bind it within the bind group.
+        debug!("Set new texture for wallpaper ...");
+        let (image_texture, sampler, bind_group) = Self::upload_image(gpu, &content, &img.rgba);
+        self.texture_width = image_texture.width();
+        self.texture_height = image_texture.height();
+        self.image_texture = image_texture;
+        self.sampler = sampler;
+        self.bind_group = bind_group;
+
+        self.prepare_content(gpu, content, &img.paddings);
+    }
+
+    /// Prepare everything drawn along with the uploaded image texture. The padding is drawn from
+    /// the copies of the image in `paddings`, which are made when decoding it.
+    fn prepare_content(&mut self, gpu: &Gpu, content: Content, paddings: &PaddingImages) {
+        // Solid colors and gradients are drawn by their own pipeline instead.
+        self.gradient = content.procedural_gradient().map(|gradient| {
+            debug!("Trying to build the gradient render pipeline ...");
+            GradientPass::new(&gpu.device, gradient, self.size, OffScreen::format())
+        });
+
+        // Re-filling the vertex buffer.
+        self.content = content;
+        debug!("Re-filling the vertex buffer with the new resize option ...");
+        let vertex_buffer = self.image_vertices();
+        gpu.queue
+            .write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&vertex_buffer));
+
+        // Tiles always cover the whole screen, so there is no padding.
+        self.padding = if self.gradient.is_none()
+            && self.content.resize_option != server_cli::ResizeOption::Tile
+        {
+            Padding::new(
+                &gpu.device,
+                &gpu.queue,
+                self.content.fill,
+                utils::rgb_f64_to_u8(self.content.fill_rgb),
+                paddings,
+                &self.image_texture,
+                self.size,
+                OffScreen::format(),
+                &vertex_buffer,
+                self.content.source_transform,
+            )
+        } else {
+            None
+        };
+    }
+
+    /// Start a transition from the old wallpaper to the new content, which is already decoded as
+    /// `img`. If the transition cannot be created, the final frame will be drawn immediately.
+    #[allow(clippy::too_many_arguments)]
+    #[tracing::instrument(skip_all, fields(name = ?self.name))]
+    pub fn start_transition(
+        &mut self,
+        gpu: &Gpu,
+        qh: &QueueHandle<Wallpaper>,
+        content: Content,
+        img: &DecodedImage,
+        duration: f64,
+        fps: f64,
+        transition_kind: TransitionKind,
+        transition_options: TransitionOptions,
+        ease_kind: EaseKind,
+        task_handle: Option<Arc<TaskHandle>>,
+    ) {
+        // Nothing has been displayed to transition from before the first configure.
+        if !self.first_configured {
+            self.change_image_and_request_frame(gpu, qh, content, img);
+            return;
+        }
+
+        // Before we do any further rendering, grab the current buffer out for later use.
+        debug!("Saving the old wallpaper ...");
+        let old_texture_view = if let Some(interrupted_transition) = self.transition.take() {
+            debug!("Found on-fly transition! Saving its current frame instead ...");
+            interrupted_transition
+                .into_frame()
+                .create_view(&texture::image_view_desc(Some("Old transition frame")))
+        } else {
+            debug!("Re-rendering the old wallpaper ...");
+            let old_buffer = OffScreen::create(&gpu.device, self.size, self.config.format);
+            let mut encoder = gpu
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+            old_buffer.update_pass(&mut encoder, self.content.fill_rgb, &self.draws(gpu));
+            gpu.queue.submit(Some(encoder.finish()));
+            old_buffer
+                .into_frame()
+                .create_view(&texture::image_view_desc(Some("Old texture view")))
+        };
+
+        self.change_image_and_request_frame(gpu, qh, content, img);
+
+        // The transition shader need the final texture view, so we need to render the new image to
+        // the off-screen buffer. Then we can get the texture view from the off-screen buffer.
+        debug!("Rendering the new image to the off-screen buffer ...");
+        let mut encoder = gpu
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        self.off_screen_buffer.resize(&gpu.device, self.size);
+        self.off_screen_buffer
+            .update_pass(&mut encoder, self.content.fill_rgb, &self.draws(gpu));
+        gpu.queue.submit(Some(encoder.finish()));
+
+        let new_texture_view = self
+            .off_screen_buffer
+            .current_frame()
+            .create_view(&texture::image_view_desc(Some("New texture view")));
+
+        let now = std::time::Instant::now();
+        debug!("Transition meta data: now={now:?}, duration={duration}, fps={fps}");
+
+        let transition = match shaders::transition::create_transition(
+            &gpu.device,
+            // Render to off-screen buffer first
+            OffScreen::format(),
+            old_texture_view,
+            new_texture_view,
+            transition_kind,
+            transition_options,
+        ) {
+            Some(t) => t,
+            // Because we already request a new frame, the final frame will be drawn in the next
+            // frame.
+            None => return,
+        };
+
+        let easing_function = ease::create_easing_curve(ease_kind);
+        let transition = TransitionState::new(
+            &gpu.device,
+            now,
+            self.transition_speed,
+            duration,
+            fps,
+            transition,
+            easing_function,
+            self.size,
+            self.config.format,
+            task_handle,
+        );
+        transition.set_transform(&gpu.queue, self.transform);
+
+        if self.transition.replace(transition).is_some() {
+            // Anyway, if `TaskHub` functions correctly or the old transition is taken by this
+            // function already, we won't find unfinished transitions here.
+            error!(
+                "Found unfinished transition! The old one will be finished immediately\
+                and start a new one!"
+            );
+        }
+    }
+
+    /// Draw calls to render the current wallpaper into an off-screen buffer.
+    fn draws<'a>(&'a self, gpu: &'a Gpu) -> Vec<Draw<'a>> {
+        if let Some(gradient) = &self.gradient {
+            return vec![gradient.draw(&gpu.index_buffer)];
+        }
+
+        let image = Draw {
+            render_pipeline: &gpu.render_pipeline,
+            bind_group: &self.bind_group,
+            vertex_buffer: &self.vertex_buffer,
+            index_buffer: &gpu.index_buffer,
+            index_count: NUM_INDEX,
+        };
+
+        match &self.padding {
+            Some(padding) => vec![padding.draw(&gpu.render_pipeline, &gpu.index_buffer), image],
+            None => vec![image],
+        }
+    }
+
+    fn image_vertices(&self) -> Vec<vertex::Vertex> {
+        vertex::create_vertex_buffer_with_resize_option(
+            self.size,
+            (self.texture_width, self.texture_height),
+            self.content.resize_option,
+            self.content.tile,
+            self.content.placement,
+            self.content.source_transform,
+        )
+    }
+
+    fn upload_image(
+        gpu: &Gpu,
+        content: &Content,
+        img: &RgbaImage,
+    ) -> (wgpu::Texture, wgpu::Sampler, wgpu::BindGroup) {
+        let texture_width = img.width();
+        let texture_height = img.height();
+        let image_texture = {
+            debug!("Trying to create and write to the texture ...");
+            let size = texture::texture_size(texture_width, texture_height);
+            let desc = texture::image_srgb_unorm_desc(None, size, 1);
+            let texture = gpu.device.create_texture(&desc);
+            gpu.queue.write_texture(
+                wgpu::TexelCopyTextureInfo {
+                    texture: &texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                img,
+                wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(4 * texture_width),
+                    rows_per_image: Some(texture_height),
+                },
+                size,
+            );
+
+            texture
+        };
+
+        // Tiling needs a repeating sampler, and integer scaling needs nearest filtering.
+        debug!("Trying to create a sampler ...");
+        let sampler = Self::create_sampler(&gpu.device, content);
+
+        debug!("Trying to bind the texture and the sampler together ...");
+        let image_texture_view = image_texture.create_view(&texture::image_view_desc(None));
+        let layout = gpu
+            .device
+            .create_bind_group_layout(&bind_group::texture_and_sampler::layout_desc(None));
+        let bind_group = bind_group::texture_and_sampler::bind_group(
+            &gpu.device,
+            None,
+            &layout,
+            &image_texture_view,
+            &sampler,
+        );
+
+        (image_texture, sampler, bind_group)
+    }
+
+    fn create_sampler(device: &wgpu::Device, content: &Content) -> wgpu::Sampler {
+        let (mag_filter, min_filter, mipmap_filter) = content.filters;
+        let address_mode = vertex::address_mode(content.resize_option, content.tile);
+        // Pixel art must not be smeared by linear filtering.
+        let (mag_filter, min_filter) = match content.resize_option {
+            server_cli::ResizeOption::Integer | server_cli::ResizeOption::IntegerCrop => {
+                (wgpu::FilterMode::Nearest, wgpu::FilterMode::Nearest)
+            }
+            _ => (mag_filter, min_filter),
+        };
+        device.create_sampler(&sampler::desc(
+            None,
+            (address_mode, address_mode),
+            mag_filter,
+            min_filter,
+            mipmap_filter,
+        ))
+    }
+}
@@ -3,9 +3,10 @@ use crate::{
     server::TaskHandle,
     wallpaper::{off_screen::OffScreen, shaders::transition::TransitionPass, texture},
 };
-use std::time::Instant;
+use std::{sync::Arc, time::Instant};
 use thiserror::Error;
 use tracing::debug;
+use wayland_client::protocol::wl_output::Transform;
 
 #[derive(Error, Debug)]
 pub enum TransitionRenderError {
@@ -15,8 +16,62 @@ pub enum TransitionRenderError {
     Finished,
 }
 
+/// The time elapsed within a transition, which can be paused, moved, and run faster or slower
+/// than the real time.
+struct TransitionClock {
+    /// Seconds elapsed within the transition at `last_tick`.
+    elapsed: f64,
+    last_tick: Instant,
+    speed: f64,
+    paused: bool,
+}
+
+impl TransitionClock {
+    fn new(start: Instant, speed: f64) -> Self {
+        Self {
+            elapsed: 0.0,
+            last_tick: start,
+            speed,
+            paused: false,
+        }
+    }
+
+    /// Advance the clock to `now`, and return the seconds elapsed within the transition.
+    fn tick(&mut self, now: Instant) -> f64 {
+        if !self.paused {
+            let real = now.saturating_duration_since(self.last_tick).as_secs_f64();
+            self.elapsed += real * self.speed;
+        }
+        self.last_tick = self.last_tick.max(now);
+        self.elapsed
+    }
+
+    fn pause(&mut self, now: Instant) {
+        self.tick(now);
+        self.paused = true;
+    }
+
+    fn resume(&mut self, now: Instant) {
+        self.tick(now);
+        self.paused = false;
+    }
+
+    /// Move to `progress` within `[0, 1]` of a transition of `duration` seconds.
+    fn seek(&mut self, now: Instant, progress: f64, duration: f64) {
+        self.tick(now);
+        self.elapsed = progress.clamp(0.0, 1.0) * duration;
+    }
+
+    fn set_speed(&mut self, now: Instant, speed: f64) {
+        self.tick(now);
+        self.speed = speed;
+    }
+}
+
 pub struct TransitionState {
+    /// Frames are paced by the real time since `start`, even if the clock runs slower.
     start: Instant,
+    clock: TransitionClock,
     duration: f64,
     fps: f64,
     last_rendered: Instant,
@@ -24,7 +79,11 @@ pub struct TransitionState {
     easing_function: Box<dyn Curve>,
     off_screen_buffer: OffScreen,
     first_rendered: bool,
-    _task_handle: Option<TaskHandle>,
+    /// The clock is moved since the last frame, which must be rendered again.
+    moved: bool,
+    /// Shared by the transitions of all outputs started by the same request.
+    /// Released once the transition is paused, so that it doesn't hold other requests back.
+    task_handle: Option<Arc<TaskHandle>>,
 }
 
 impl TransitionState {
@@ -32,17 +91,19 @@ impl TransitionState {
     pub fn new(
         device: &wgpu::Device,
         start: Instant,
+        speed: f64,
         duration: f64,
         fps: f64,
         transition: Box<dyn TransitionPass>,
         easing_function: Box<dyn Curve>,
         size: (u32, u32),
         target_format: wgpu::TextureFormat,
-        task_handle: Option<TaskHandle>,
+        task_handle: Option<Arc<TaskHandle>>,
     ) -> Self {
         let off_screen_buffer = OffScreen::create(device, size, target_format);
         Self {
             start,
+            clock: TransitionClock::new(start, speed),
             duration,
             fps,
             last_rendered: start,
@@ -50,7 +111,8 @@ impl TransitionState {
             easing_function,
             off_screen_buffer,
             first_rendered: false,
-            _task_handle: task_handle,
+            moved: false,
+            task_handle,
         }
     }
 
@@ -65,7 +127,7 @@ impl TransitionState {
     ) -> Result<(), TransitionRenderError> {
         debug!("Start transition render pass ...");
 
-        let elapsed_seconds = (now - self.start).as_secs_f64();
+        let elapsed_seconds = self.clock.tick(now);
         debug!("elapsed_seconds={elapsed_seconds}");
         if elapsed_seconds > self.duration {
             debug!(
@@ -75,16 +137,17 @@ impl TransitionState {
             return Err(TransitionRenderError::Finished);
         }
 
+        let real_seconds = now.saturating_duration_since(self.start).as_secs_f64();
         let last_seconds = (self.last_rendered - self.start).as_secs_f64();
         let frame_duration = 1.0 / self.fps;
         debug!("last_seconds={last_seconds}, frame_duration={frame_duration}");
 
-        let current_frame = (elapsed_seconds / frame_duration).floor() as i64;
+        let current_frame = (real_seconds / frame_duration).floor() as i64;
         let last_frame = (last_seconds / frame_duration).floor() as i64;
         debug!("current_frame={current_frame}, last_frame={last_frame}");
-        if current_frame == last_frame && self.first_rendered {
+        if current_frame == last_frame && self.first_rendered && !self.moved {
             debug!(
-                "elapsed_seconds `{elapsed_seconds}` and \
+                "real_seconds `{real_seconds}` and \
                 last `{last_seconds}` are in the same frame"
             );
 
@@ -95,6 +158,7 @@ impl TransitionState {
 
         debug!("New frame rendering");
         self.first_rendered |= true;
+        self.moved = false;
         self.last_rendered = now;
         let off_screen_view =
             self.off_screen_buffer
@@ -118,12 +182,104 @@ impl TransitionState {
         Ok(())
     }
 
-    #[allow(unused)]
-    pub fn current_frame(&self) -> &wgpu::Texture {
-        self.off_screen_buffer.current_frame()
+    /// Freeze the progress at `now`. Frames are no longer needed until it's resumed, and the
+    /// transition can be interrupted by new requests even with `--no-interrupt`.
+    pub fn pause(&mut self, now: Instant) {
+        self.clock.pause(now);
+        self.task_handle = None;
+    }
+
+    pub fn resume(&mut self, now: Instant) {
+        self.clock.resume(now);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.clock.paused
+    }
+
+    /// Move to `progress` within `[0, 1]`, before easing.
+    pub fn seek(&mut self, now: Instant, progress: f64) {
+        self.clock.seek(now, progress, self.duration);
+        self.moved = true;
+    }
+
+    /// Run at `speed` times the real speed from `now` on.
+    pub fn set_speed(&mut self, now: Instant, speed: f64) {
+        self.clock.set_speed(now, speed);
+    }
+
+    pub fn set_transform(&self, queue: &wgpu::Queue, transform: Transform) {
+        self.off_screen_buffer.set_transform(queue, transform);
+    }
+
+    /// The frame last rendered, or `None` before the first frame is rendered.
+    pub fn current_frame(&self) -> Option<&wgpu::Texture> {
+        self.first_rendered
+            .then(|| self.off_screen_buffer.current_frame())
     }
 
     pub fn into_frame(self) -> wgpu::Texture {
         self.off_screen_buffer.into_frame()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::TransitionClock;
+    use std::time::{Duration, Instant};
+
+    fn secs(secs: f64) -> Duration {
+        Duration::from_secs_f64(secs)
+    }
+
+    #[test]
+    fn test_clock_pause_resume() {
+        let start = Instant::now();
+        let mut clock = TransitionClock::new(start, 1.0);
+        assert_eq!(clock.tick(start + secs(1.0)), 1.0);
+
+        // The elapsed time is frozen while paused, and continues from there once resumed.
+        clock.pause(start + secs(1.5));
+        assert_eq!(clock.tick(start + secs(2.0)), 1.5);
+        assert_eq!(clock.tick(start + secs(10.0)), 1.5);
+        clock.resume(start + secs(11.0));
+        assert_eq!(clock.tick(start + secs(11.0)), 1.5);
+        assert_eq!(clock.tick(start + secs(12.0)), 2.5);
+    }
+
+    #[test]
+    fn test_clock_speed() {
+        let start = Instant::now();
+        let mut clock = TransitionClock::new(start, 2.0);
+        assert_eq!(clock.tick(start + secs(1.0)), 2.0);
+
+        // The time before changing the speed isn't scaled again.
+        clock.set_speed(start + secs(2.0), 0.5);
+        assert_eq!(clock.tick(start + secs(2.0)), 4.0);
+        assert_eq!(clock.tick(start + secs(4.0)), 5.0);
+
+        // Ticks earlier than the last one don't move the clock back.
+        assert_eq!(clock.tick(start + secs(3.0)), 5.0);
+        assert_eq!(clock.tick(start + secs(6.0)), 6.0);
+    }
+
+    #[test]
+    fn test_clock_seek() {
+        let start = Instant::now();
+        let mut clock = TransitionClock::new(start, 1.0);
+
+        clock.seek(start + secs(1.0), 0.5, 4.0);
+        assert_eq!(clock.tick(start + secs(1.0)), 2.0);
+        assert_eq!(clock.tick(start + secs(2.0)), 3.0);
+
+        clock.seek(start + secs(2.0), 1.5, 4.0);
+        assert_eq!(clock.tick(start + secs(2.0)), 4.0);
+        clock.seek(start + secs(2.0), -0.5, 4.0);
+        assert_eq!(clock.tick(start + secs(2.0)), 0.0);
+
+        // Seeking while paused moves the frozen time.
+        clock.pause(start + secs(2.0));
+        clock.seek(start + secs(3.0), 0.25, 4.0);
+        assert_eq!(clock.tick(start + secs(5.0)), 1.0);
+    }
+}
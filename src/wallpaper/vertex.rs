@@ -1,5 +1,6 @@
 use common::cli::server as server_cli;
 use std::mem;
+use wayland_client::protocol::wl_output::Transform;
 
 #[repr(C)]
 #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
@@ -78,31 +79,730 @@ pub fn create_vertex_buffer_with_resize_option(
     surface_size: (u32, u32),
     texture_size: (u32, u32),
     resize_option: server_cli::ResizeOption,
+    tile: server_cli::TileOptions,
+    placement: server_cli::Placement,
+    source_transform: server_cli::SourceTransform,
+) -> Vec<Vertex> {
+    // Repeating samplers always wrap around the whole texture, so a part of it cannot be tiled.
+    let crop = match resize_option {
+        server_cli::ResizeOption::Tile => None,
+        _ => source_transform
+            .crop
+            .map(|crop| clamp_crop(crop, texture_size)),
+    };
+
+    // The size of the image as it is displayed, after cropping and rotating it.
+    let (tx, ty) = crop.map_or(texture_size, |(_, _, w, h)| (w, h));
+    let displayed_size = match source_transform.rotate {
+        Some(server_cli::Rotation::Rotate90 | server_cli::Rotation::Rotate270) => (ty, tx),
+        _ => (tx, ty),
+    };
+
+    let mut vertices = layout(surface_size, displayed_size, resize_option, tile, placement);
+    for vertex in &mut vertices {
+        vertex.texture_coords =
+            source_coords(vertex.texture_coords, source_transform, crop, texture_size);
+    }
+
+    vertices
+}
+
+/// Keep the crop rectangle inside the texture.
+pub fn clamp_crop(
+    (x, y, w, h): (u32, u32, u32, u32),
+    (tx, ty): (u32, u32),
+) -> (u32, u32, u32, u32) {
+    let (x, y) = (x.min(tx - 1), y.min(ty - 1));
+    (x, y, w.min(tx - x), h.min(ty - y))
+}
+
+/// Map texture coordinates of the displayed image to texture coordinates of the source image,
+/// by undoing flipping, rotation and cropping in order.
+fn source_coords(
+    [u, v]: [f32; 2],
+    source_transform: server_cli::SourceTransform,
+    crop: Option<(u32, u32, u32, u32)>,
+    (tx, ty): (u32, u32),
+) -> [f32; 2] {
+    let (u, v) = match source_transform.flip {
+        Some(server_cli::Flip::Horizontal) => (1.0 - u, v),
+        Some(server_cli::Flip::Vertical) => (u, 1.0 - v),
+        None => (u, v),
+    };
+
+    let (u, v) = match source_transform.rotate {
+        Some(server_cli::Rotation::Rotate90) => (v, 1.0 - u),
+        Some(server_cli::Rotation::Rotate180) => (1.0 - u, 1.0 - v),
+        Some(server_cli::Rotation::Rotate270) => (1.0 - v, u),
+        None => (u, v),
+    };
+
+    match crop {
+        Some((x, y, w, h)) => [
+            (x as f32 + u * w as f32) / tx as f32,
+            (y as f32 + v * h as f32) / ty as f32,
+        ],
+        None => [u, v],
+    }
+}
+
+/// Create the quad of an upright image of `texture_size`, with texture coordinates from `0.0` to
+/// `1.0`, or beyond for tiling.
+fn layout(
+    surface_size: (u32, u32),
+    texture_size: (u32, u32),
+    resize_option: server_cli::ResizeOption,
+    tile: server_cli::TileOptions,
+    placement: server_cli::Placement,
 ) -> Vec<Vertex> {
     let (sx, sy) = surface_size;
     let (tx, ty) = texture_size;
+    let integer = matches!(
+        resize_option,
+        server_cli::ResizeOption::Integer | server_cli::ResizeOption::IntegerCrop
+    );
+    let zoom = placement.zoom.unwrap_or(server_cli::DEFAULT_ZOOM) as f32;
 
-    match resize_option {
-        server_cli::ResizeOption::No => {
-            let (xf, yf) = (tx as f32 / sx as f32, ty as f32 / sy as f32);
-            vertices!(xf, -xf, yf, -yf, 1.0, 1.0)
-        }
+    // Half of the width and height of the resized image, in clip space.
+    let (hx, hy) = match resize_option {
+        server_cli::ResizeOption::No => (tx as f32 / sx as f32, ty as f32 / sy as f32),
         server_cli::ResizeOption::Crop => {
             let (xf, yf) = (tx as f32 / sx as f32, ty as f32 / sy as f32);
             if xf < yf {
-                vertices!(1.0, -1., yf / xf, -yf / xf, 1.0, 1.0)
+                (1.0, yf / xf)
             } else {
-                vertices!(xf / yf, -xf / yf, 1.0, -1., 1.0, 1.0)
+                (xf / yf, 1.0)
             }
         }
         server_cli::ResizeOption::Fit => {
             let (xf, yf) = (tx as f32 / sx as f32, ty as f32 / sy as f32);
             if xf > yf {
-                vertices!(1.0, -1., yf / xf, -yf / xf, 1.0, 1.0)
+                (1.0, yf / xf)
             } else {
-                vertices!(xf / yf, -xf / yf, 1.0, -1., 1.0, 1.0)
+                (xf / yf, 1.0)
             }
         }
-        server_cli::ResizeOption::Stretch => vertices!(1.0, -1., 1.0, -1., 1.0, 1.0),
+        server_cli::ResizeOption::Stretch => (1.0, 1.0),
+        server_cli::ResizeOption::Integer | server_cli::ResizeOption::IntegerCrop => {
+            let (xf, yf) = (sx as f32 / tx as f32, sy as f32 / ty as f32);
+            let scale = if resize_option == server_cli::ResizeOption::IntegerCrop {
+                xf.max(yf).ceil()
+            } else {
+                xf.min(yf).floor()
+            };
+            // Zooming must keep the scale a whole number as well.
+            let scale = (scale * zoom).round().max(1.0);
+            (scale * tx as f32 / sx as f32, scale * ty as f32 / sy as f32)
+        }
+        server_cli::ResizeOption::Tile => {
+            let scale = tile.scale.unwrap_or(server_cli::DEFAULT_TILE_SCALE) as f32;
+            let (ox, oy) = tile.offset.unwrap_or(server_cli::DEFAULT_TILE_OFFSET);
+            let (ox, oy) = (ox as f32, oy as f32);
+            // Size of a single tile in pixels. The sampler repeats the texture beyond 1.0.
+            let (tw, th) = (tx as f32 * scale, ty as f32 * scale);
+            return surface_quad(
+                -ox / tw,
+                (sx as f32 - ox) / tw,
+                -oy / th,
+                (sy as f32 - oy) / th,
+            );
+        }
+    };
+
+    let zoom = if integer { 1.0 } else { zoom };
+    let (hx, hy) = (hx * zoom, hy * zoom);
+
+    // Center of the image in clip space. Image coordinates go downwards, while clip space
+    // coordinates go upwards, hence the flipped `y` axis.
+    let (cx, cy) = match placement.focal_point {
+        Some((fx, fy)) => (focus(fx as f32, hx), -focus(fy as f32, hy)),
+        None => {
+            let (ax, ay) = align_factors(placement.align.unwrap_or(server_cli::DEFAULT_ALIGN));
+            (align(ax, hx), -align(ay, hy))
+        }
+    };
+
+    let (px, py) = placement.pan.unwrap_or(server_cli::DEFAULT_PAN);
+    let (cx, cy) = (
+        cx + 2.0 * px as f32 / sx as f32,
+        cy - 2.0 * py as f32 / sy as f32,
+    );
+
+    // Every texel must cover the same number of whole pixels.
+    let (cx, cy) = if integer {
+        (snap(cx, hx, sx), -snap(-cy, hy, sy))
+    } else {
+        (cx, cy)
+    };
+
+    vertices!(cx + hx, cx - hx, cy + hy, cy - hy, 1.0, 1.0)
+}
+
+/// Move the center of an image with half extent `half` along one axis, so that the edges of the
+/// image lie on whole pixels of a surface of `size` pixels.
+fn snap(center: f32, half: f32, size: u32) -> f32 {
+    let edge = ((center - half + 1.0) / 2.0 * size as f32).round();
+    edge / size as f32 * 2.0 - 1.0 + half
+}
+
+/// Normalized position of the anchor on both the screen and the image, where `0.0` is the
+/// left or top edge and `1.0` is the right or bottom edge.
+fn align_factors(align: server_cli::Align) -> (f32, f32) {
+    match align {
+        server_cli::Align::Center => (0.5, 0.5),
+        server_cli::Align::Top => (0.5, 0.0),
+        server_cli::Align::Bottom => (0.5, 1.0),
+        server_cli::Align::Left => (0.0, 0.5),
+        server_cli::Align::Right => (1.0, 0.5),
+        server_cli::Align::TopLeft => (0.0, 0.0),
+        server_cli::Align::TopRight => (1.0, 0.0),
+        server_cli::Align::BottomLeft => (0.0, 1.0),
+        server_cli::Align::BottomRight => (1.0, 1.0),
+    }
+}
+
+/// Center of an image with half extent `half` along one axis, so that the anchor at `factor` of
+/// the image meets the anchor at `factor` of the screen. It works for both images larger and
+/// smaller than the screen.
+fn align(factor: f32, half: f32) -> f32 {
+    (2.0 * factor - 1.0) * (1.0 - half)
+}
+
+/// Center of an image with half extent `half` along one axis, so that the focal point at `focal`
+/// of the image is moved to the center of the screen, without moving the image edges into the
+/// screen if it is larger than the screen, or out of the screen if it is smaller.
+fn focus(focal: f32, half: f32) -> f32 {
+    let limit = (1.0 - half).abs();
+    (-(2.0 * focal - 1.0) * half).clamp(-limit, limit)
+}
+
+/// Whether the buffer is rotated by 90 or 270 degrees with the output transform, so its width and
+/// height are swapped.
+pub fn swaps_axes(transform: Transform) -> bool {
+    matches!(
+        transform,
+        Transform::_90 | Transform::_270 | Transform::Flipped90 | Transform::Flipped270
+    )
+}
+
+/// Cover the whole buffer with the upright content, which is pre-transformed with the output
+/// transform. See `wl_output.transform`: the content is flipped around the vertical axis first,
+/// then rotated counter-clockwise.
+pub fn transformed_vertices(transform: Transform) -> Vec<Vertex> {
+    let flipped = matches!(
+        transform,
+        Transform::Flipped | Transform::Flipped90 | Transform::Flipped180 | Transform::Flipped270
+    );
+
+    // Map the texture coordinates of the buffer back to those of the content, by undoing the
+    // rotation, then the flip.
+    let coords = |u: f32, v: f32| {
+        let (u, v) = match transform {
+            Transform::_90 | Transform::Flipped90 => (1.0 - v, u),
+            Transform::_180 | Transform::Flipped180 => (1.0 - u, 1.0 - v),
+            Transform::_270 | Transform::Flipped270 => (v, 1.0 - u),
+            _ => (u, v),
+        };
+
+        if flipped { [1.0 - u, v] } else { [u, v] }
+    };
+
+    STRETCH_VERTICES
+        .iter()
+        .map(|vertex| {
+            let [u, v] = vertex.texture_coords;
+            Vertex {
+                position: vertex.position,
+                texture_coords: coords(u, v),
+            }
+        })
+        .collect()
+}
+
+/// Sampler address mode needed to draw the texture with the given resize option.
+pub fn address_mode(
+    resize_option: server_cli::ResizeOption,
+    tile: server_cli::TileOptions,
+) -> wgpu::AddressMode {
+    match resize_option {
+        server_cli::ResizeOption::Tile if tile.mirror => wgpu::AddressMode::MirrorRepeat,
+        server_cli::ResizeOption::Tile => wgpu::AddressMode::Repeat,
+        _ => wgpu::AddressMode::ClampToEdge,
+    }
+}
+
+/// Cover the whole surface with the texture of an axis-aligned quad. Texture coordinates are
+/// extended beyond the edges of the quad, so that the texture is still drawn at the same place
+/// inside the quad, and the sampler address mode decides what is drawn outside it.
+pub fn extend_to_surface(vertices: &[Vertex]) -> Vec<Vertex> {
+    // Top-right, top-left and bottom-left corners. See `vertices!`. Texture coordinates may be
+    // rotated, so both of them can change along either axis.
+    let (tr, tl, bl) = (vertices[0], vertices[1], vertices[2]);
+    let coords = |x: f32, y: f32| {
+        let fx = (x - tl.position[0]) / (tr.position[0] - tl.position[0]);
+        let fy = (y - tl.position[1]) / (bl.position[1] - tl.position[1]);
+        [0, 1].map(|i| {
+            tl.texture_coords[i]
+                + fx * (tr.texture_coords[i] - tl.texture_coords[i])
+                + fy * (bl.texture_coords[i] - tl.texture_coords[i])
+        })
+    };
+
+    Vec::from([
+        Vertex {
+            position: [1.0, 1.0, 0.0],
+            texture_coords: coords(1.0, 1.0),
+        },
+        Vertex {
+            position: [-1., 1.0, 0.0],
+            texture_coords: coords(-1., 1.0),
+        },
+        Vertex {
+            position: [-1., -1., 0.0],
+            texture_coords: coords(-1., -1.),
+        },
+        Vertex {
+            position: [1.0, -1., 0.0],
+            texture_coords: coords(1.0, -1.),
+        },
+    ])
+}
+
+/// Map texture coordinates of the whole texture of `texture_size` to texture coordinates of its
+/// `crop` rectangle, e.g. to draw from a copy of the rectangle.
+pub fn to_crop_coords(
+    vertices: &[Vertex],
+    (x, y, w, h): (u32, u32, u32, u32),
+    (tx, ty): (u32, u32),
+) -> Vec<Vertex> {
+    vertices
+        .iter()
+        .map(|vertex| {
+            let [u, v] = vertex.texture_coords;
+            Vertex {
+                texture_coords: [
+                    (u * tx as f32 - x as f32) / w as f32,
+                    (v * ty as f32 - y as f32) / h as f32,
+                ],
+                ..*vertex
+            }
+        })
+        .collect()
+}
+
+/// A quad covering the whole surface, with the given texture coordinates on its edges.
+fn surface_quad(left: f32, right: f32, top: f32, bottom: f32) -> Vec<Vertex> {
+    Vec::from([
+        Vertex {
+            position: [1.0, 1.0, 0.0],
+            texture_coords: [right, top],
+        },
+        Vertex {
+            position: [-1., 1.0, 0.0],
+            texture_coords: [left, top],
+        },
+        Vertex {
+            position: [-1., -1., 0.0],
+            texture_coords: [left, bottom],
+        },
+        Vertex {
+            position: [1.0, -1., 0.0],
+            texture_coords: [right, bottom],
+        },
+    ])
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        Vertex, clamp_crop, create_vertex_buffer_with_resize_option, extend_to_surface, layout,
+        snap, to_crop_coords, transformed_vertices,
+    };
+    use common::cli::server as server_cli;
+    use wayland_client::protocol::wl_output::Transform;
+
+    fn positions(vertices: &[Vertex]) -> Vec<[f32; 2]> {
+        vertices
+            .iter()
+            .map(|vertex| [vertex.position[0], vertex.position[1]])
+            .collect()
+    }
+
+    fn texture_coords(vertices: &[Vertex]) -> Vec<[f32; 2]> {
+        vertices
+            .iter()
+            .map(|vertex| vertex.texture_coords)
+            .collect()
+    }
+
+    /// Compare the top-right, top-left, bottom-left and bottom-right corners of a quad.
+    fn assert_close(actual: &[[f32; 2]], expected: &[[f32; 2]]) {
+        assert_eq!(actual.len(), expected.len());
+        for (a, e) in actual.iter().zip(expected) {
+            assert!(
+                (a[0] - e[0]).abs() < 1e-5 && (a[1] - e[1]).abs() < 1e-5,
+                "{actual:?} != {expected:?}"
+            );
+        }
+    }
+
+    /// The quad of a `texture_size` image on a 200x100 surface.
+    fn placed(
+        texture_size: (u32, u32),
+        resize_option: server_cli::ResizeOption,
+        placement: server_cli::Placement,
+    ) -> Vec<Vertex> {
+        layout(
+            (200, 100),
+            texture_size,
+            resize_option,
+            server_cli::TileOptions::default(),
+            placement,
+        )
+    }
+
+    /// The quad from `left` to `right` and from `top` to `bottom` in clip space.
+    fn quad(left: f32, right: f32, top: f32, bottom: f32) -> [[f32; 2]; 4] {
+        [[right, top], [left, top], [left, bottom], [right, bottom]]
+    }
+
+    const UPRIGHT: [[f32; 2]; 4] = [[1.0, 0.0], [0.0, 0.0], [0.0, 1.0], [1.0, 1.0]];
+
+    fn align(align: server_cli::Align) -> server_cli::Placement {
+        server_cli::Placement {
+            align: Some(align),
+            ..Default::default()
+        }
+    }
+
+    fn focal_point(x: f64, y: f64) -> server_cli::Placement {
+        server_cli::Placement {
+            focal_point: Some((x, y)),
+            ..Default::default()
+        }
+    }
+
+    fn zoom(zoom: f64) -> server_cli::Placement {
+        server_cli::Placement {
+            zoom: Some(zoom),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_align() {
+        use server_cli::{
+            Align,
+            ResizeOption::{Crop, Fit, No},
+        };
+
+        // A square image is cropped to 200x200 and fitted to 100x100 on a 200x100 surface.
+        let cases = [
+            (Crop, Align::Center, quad(-1.0, 1.0, 2.0, -2.0)),
+            (Crop, Align::Top, quad(-1.0, 1.0, 1.0, -3.0)),
+            (Crop, Align::Bottom, quad(-1.0, 1.0, 3.0, -1.0)),
+            (Fit, Align::Center, quad(-0.5, 0.5, 1.0, -1.0)),
+            (Fit, Align::Left, quad(-1.0, 0.0, 1.0, -1.0)),
+            (Fit, Align::Right, quad(0.0, 1.0, 1.0, -1.0)),
+            (Fit, Align::BottomRight, quad(0.0, 1.0, 1.0, -1.0)),
+        ];
+        for (resize_option, a, expected) in cases {
+            let vertices = placed((100, 100), resize_option, align(a));
+            assert_close(&positions(&vertices), &expected);
+            assert_close(&texture_coords(&vertices), &UPRIGHT);
+        }
+
+        // A 50x50 image isn't resized.
+        let vertices = placed((50, 50), No, align(Align::TopLeft));
+        assert_close(&positions(&vertices), &quad(-1.0, -0.5, 1.0, 0.0));
+        let vertices = placed((50, 50), No, align(Align::BottomRight));
+        assert_close(&positions(&vertices), &quad(0.5, 1.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn test_focal_point() {
+        use server_cli::ResizeOption::{Crop, Fit};
+
+        // The edges of an image larger than the screen are kept on the screen edges.
+        let cases = [
+            (0.5, 0.5, quad(-1.0, 1.0, 2.0, -2.0)),
+            (0.5, 0.0, quad(-1.0, 1.0, 1.0, -3.0)),
+            (0.5, 1.0, quad(-1.0, 1.0, 3.0, -1.0)),
+            (0.0, 0.25, quad(-1.0, 1.0, 1.0, -3.0)),
+        ];
+        for (x, y, expected) in cases {
+            let vertices = placed((100, 100), Crop, focal_point(x, y));
+            assert_close(&positions(&vertices), &expected);
+            assert_close(&texture_coords(&vertices), &UPRIGHT);
+        }
+
+        // The edges of an image smaller than the screen are kept inside the screen.
+        let cases = [
+            (0.0, 0.5, quad(0.0, 1.0, 1.0, -1.0)),
+            (1.0, 0.5, quad(-1.0, 0.0, 1.0, -1.0)),
+            (0.25, 0.0, quad(-0.25, 0.75, 1.0, -1.0)),
+        ];
+        for (x, y, expected) in cases {
+            let vertices = placed((100, 100), Fit, focal_point(x, y));
+            assert_close(&positions(&vertices), &expected);
+        }
+    }
+
+    #[test]
+    fn test_zoom() {
+        use server_cli::{
+            Align,
+            ResizeOption::{Crop, Fit},
+        };
+
+        let vertices = placed((100, 100), Crop, zoom(2.0));
+        assert_close(&positions(&vertices), &quad(-2.0, 2.0, 4.0, -4.0));
+        assert_close(&texture_coords(&vertices), &UPRIGHT);
+
+        let vertices = placed((100, 100), Fit, zoom(0.5));
+        assert_close(&positions(&vertices), &quad(-0.25, 0.25, 0.5, -0.5));
+        assert_close(&texture_coords(&vertices), &UPRIGHT);
+
+        // The zoomed image is aligned, not the image before zooming.
+        let placement = server_cli::Placement {
+            align: Some(Align::TopLeft),
+            zoom: Some(0.5),
+            ..Default::default()
+        };
+        let vertices = placed((100, 100), Fit, placement);
+        assert_close(&positions(&vertices), &quad(-1.0, -0.5, 1.0, 0.0));
+        let placement = server_cli::Placement {
+            align: Some(Align::TopLeft),
+            zoom: Some(2.0),
+            ..Default::default()
+        };
+        let vertices = placed((100, 100), Fit, placement);
+        assert_close(&positions(&vertices), &quad(-1.0, 1.0, 1.0, -3.0));
+    }
+
+    /// The left, right, top and bottom edges of a quad on a 200x100 surface, in pixels from the
+    /// top-left corner.
+    fn pixel_edges(vertices: &[Vertex]) -> [f32; 4] {
+        let ([right, top, _], [left, bottom, _]) = (vertices[0].position, vertices[2].position);
+        [
+            (left + 1.0) / 2.0 * 200.0,
+            (right + 1.0) / 2.0 * 200.0,
+            (1.0 - top) / 2.0 * 100.0,
+            (1.0 - bottom) / 2.0 * 100.0,
+        ]
+    }
+
+    /// Check that the edges of a quad lie on whole pixels, and return its size in pixels.
+    fn whole_pixel_size(vertices: &[Vertex]) -> (f32, f32) {
+        let edges = pixel_edges(vertices);
+        for edge in edges {
+            assert!((edge - edge.round()).abs() < 1e-3, "{edges:?}");
+        }
+        let [left, right, top, bottom] = edges.map(f32::round);
+        (right - left, bottom - top)
+    }
+
+    #[test]
+    fn test_snap() {
+        for (center, half, size) in [(0.0, 0.5, 101), (0.3, 0.25, 200), (-0.7, 1.3, 33)] {
+            let center = snap(center, half, size);
+            let edge = (center - half + 1.0) / 2.0 * size as f32;
+            assert!((edge - edge.round()).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn test_integer() {
+        use server_cli::ResizeOption::{Integer, IntegerCrop};
+
+        // Scaled by 3 to fit, or by 7 to cover the 200x100 surface.
+        let vertices = placed((30, 30), Integer, server_cli::Placement::default());
+        assert_eq!(whole_pixel_size(&vertices), (90.0, 90.0));
+        assert_close(&texture_coords(&vertices), &UPRIGHT);
+        let vertices = placed((30, 30), IntegerCrop, server_cli::Placement::default());
+        assert_eq!(whole_pixel_size(&vertices), (210.0, 210.0));
+
+        // An image larger than the surface isn't scaled down.
+        let vertices = placed((300, 150), Integer, server_cli::Placement::default());
+        assert_eq!(whole_pixel_size(&vertices), (300.0, 150.0));
+
+        // Zooming rounds the scale to a whole number, and odd leftovers are snapped.
+        let vertices = placed((30, 30), Integer, zoom(1.4));
+        assert_eq!(whole_pixel_size(&vertices), (120.0, 120.0));
+        let vertices = placed((35, 17), IntegerCrop, zoom(0.5));
+        assert_eq!(whole_pixel_size(&vertices), (105.0, 51.0));
+
+        // Panning by a fraction of a pixel keeps the edges on whole pixels.
+        let placement = server_cli::Placement {
+            pan: Some((10.3, -4.6)),
+            ..Default::default()
+        };
+        let vertices = placed((30, 30), Integer, placement);
+        assert_eq!(whole_pixel_size(&vertices), (90.0, 90.0));
+        let [left, _, top, _] = pixel_edges(&vertices).map(f32::round);
+        assert_eq!((left, top), (65.0, 0.0));
+    }
+
+    // The rectangle of 100x50 at 40,20 of a 200x100 image, i.e. from 0.2 to 0.7 on both axes.
+    const TEXTURE_SIZE: (u32, u32) = (200, 100);
+    const CROP: (u32, u32, u32, u32) = (40, 20, 100, 50);
+
+    fn cropped(
+        resize_option: server_cli::ResizeOption,
+        rotate: Option<server_cli::Rotation>,
+        flip: Option<server_cli::Flip>,
+    ) -> Vec<Vertex> {
+        create_vertex_buffer_with_resize_option(
+            (100, 100),
+            TEXTURE_SIZE,
+            resize_option,
+            server_cli::TileOptions::default(),
+            server_cli::Placement::default(),
+            server_cli::SourceTransform {
+                crop: Some(CROP),
+                rotate,
+                flip,
+            },
+        )
+    }
+
+    #[test]
+    fn test_clamp_crop() {
+        assert_eq!(clamp_crop(CROP, TEXTURE_SIZE), CROP);
+        assert_eq!(
+            clamp_crop((150, 90, 100, 100), TEXTURE_SIZE),
+            (150, 90, 50, 10)
+        );
+        assert_eq!(
+            clamp_crop((250, 100, 10, 10), TEXTURE_SIZE),
+            (199, 99, 1, 1)
+        );
+    }
+
+    #[test]
+    fn test_crop_rotate_flip() {
+        use server_cli::{Flip, ResizeOption::Stretch, Rotation};
+
+        let cases = [
+            (None, None, [[0.7, 0.2], [0.2, 0.2], [0.2, 0.7], [0.7, 0.7]]),
+            // Rotated clockwise, the bottom-left corner of the image is at the top-left.
+            (
+                Some(Rotation::Rotate90),
+                None,
+                [[0.2, 0.2], [0.2, 0.7], [0.7, 0.7], [0.7, 0.2]],
+            ),
+            (
+                Some(Rotation::Rotate180),
+                None,
+                [[0.2, 0.7], [0.7, 0.7], [0.7, 0.2], [0.2, 0.2]],
+            ),
+            (
+                Some(Rotation::Rotate270),
+                None,
+                [[0.7, 0.7], [0.7, 0.2], [0.2, 0.2], [0.2, 0.7]],
+            ),
+            (
+                None,
+                Some(Flip::Horizontal),
+                [[0.2, 0.2], [0.7, 0.2], [0.7, 0.7], [0.2, 0.7]],
+            ),
+            (
+                None,
+                Some(Flip::Vertical),
+                [[0.7, 0.7], [0.2, 0.7], [0.2, 0.2], [0.7, 0.2]],
+            ),
+            // Flipped after rotating, which is the same as transposing the image.
+            (
+                Some(Rotation::Rotate90),
+                Some(Flip::Horizontal),
+                [[0.2, 0.7], [0.2, 0.2], [0.7, 0.2], [0.7, 0.7]],
+            ),
+        ];
+        for (rotate, flip, expected) in cases {
+            assert_close(&texture_coords(&cropped(Stretch, rotate, flip)), &expected);
+        }
+    }
+
+    #[test]
+    fn test_rotated_crop_size() {
+        use server_cli::{ResizeOption::Fit, Rotation};
+
+        // 100x50 is displayed as 50x100 after rotating it.
+        let vertices = cropped(Fit, None, None);
+        assert_close(
+            &positions(&vertices),
+            &[[1.0, 0.5], [-1.0, 0.5], [-1.0, -0.5], [1.0, -0.5]],
+        );
+        for rotate in [Rotation::Rotate90, Rotation::Rotate270] {
+            let vertices = cropped(Fit, Some(rotate), None);
+            assert_close(
+                &positions(&vertices),
+                &[[0.5, 1.0], [-0.5, 1.0], [-0.5, -1.0], [0.5, -1.0]],
+            );
+        }
+    }
+
+    #[test]
+    fn test_extend_crop() {
+        use server_cli::{ResizeOption::Fit, Rotation};
+
+        // Paddings are drawn from a copy of the crop rectangle, which is covered exactly by the
+        // image, and extended beyond it to the surface edges.
+        let vertices = to_crop_coords(&cropped(Fit, None, None), CROP, TEXTURE_SIZE);
+        assert_close(
+            &texture_coords(&vertices),
+            &[[1.0, 0.0], [0.0, 0.0], [0.0, 1.0], [1.0, 1.0]],
+        );
+        assert_close(
+            &texture_coords(&extend_to_surface(&vertices)),
+            &[[1.0, -0.5], [0.0, -0.5], [0.0, 1.5], [1.0, 1.5]],
+        );
+
+        let vertices = to_crop_coords(
+            &cropped(Fit, Some(Rotation::Rotate90), None),
+            CROP,
+            TEXTURE_SIZE,
+        );
+        assert_close(
+            &texture_coords(&vertices),
+            &[[0.0, 0.0], [0.0, 1.0], [1.0, 1.0], [1.0, 0.0]],
+        );
+        assert_close(
+            &texture_coords(&extend_to_surface(&vertices)),
+            &[[0.0, -0.5], [0.0, 1.5], [1.0, 1.5], [1.0, -0.5]],
+        );
+    }
+
+    #[test]
+    fn test_transformed_vertices() {
+        let cases = [
+            (
+                Transform::Normal,
+                [[1.0, 0.0], [0.0, 0.0], [0.0, 1.0], [1.0, 1.0]],
+            ),
+            (
+                Transform::_90,
+                [[1.0, 1.0], [1.0, 0.0], [0.0, 0.0], [0.0, 1.0]],
+            ),
+            (
+                Transform::_180,
+                [[0.0, 1.0], [1.0, 1.0], [1.0, 0.0], [0.0, 0.0]],
+            ),
+            (
+                Transform::_270,
+                [[0.0, 0.0], [0.0, 1.0], [1.0, 1.0], [1.0, 0.0]],
+            ),
+            (
+                Transform::Flipped,
+                [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]],
+            ),
+            (
+                Transform::Flipped90,
+                [[0.0, 1.0], [0.0, 0.0], [1.0, 0.0], [1.0, 1.0]],
+            ),
+        ];
+        for (transform, expected) in cases {
+            assert_close(&texture_coords(&transformed_vertices(transform)), &expected);
+        }
     }
 }
@@ -0,0 +1,85 @@
+use anyhow::Result;
+use notify::{
+    Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher,
+    event::{AccessKind, AccessMode, ModifyKind, RenameMode},
+};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+use tokio::sync::mpsc;
+use tracing::{debug, warn};
+
+/// Directories watched for changes, by inotify on Linux. Images are watched through their
+/// directories, so that images replaced by renaming a new file over them are noticed as well.
+pub struct FileWatcher {
+    watcher: RecommendedWatcher,
+    watched: HashMap<PathBuf, RecursiveMode>,
+}
+
+impl FileWatcher {
+    /// Events are sent to `tx` from the thread of the watcher, to be handled in the event loop.
+    pub fn new(tx: mpsc::UnboundedSender<Event>) -> Result<Self> {
+        let watcher = notify::recommended_watcher(move |event| match event {
+            Ok(event) => {
+                // The event loop has stopped if the receiver is dropped.
+                let _ = tx.send(event);
+            }
+            Err(e) => warn!("Failed to watch files: {e}"),
+        })?;
+
+        Ok(Self {
+            watcher,
+            watched: HashMap::new(),
+        })
+    }
+
+    /// Watch exactly `dirs`, and stop watching the directories which are not in it.
+    pub fn watch(&mut self, mut dirs: HashMap<PathBuf, RecursiveMode>) {
+        self.watched.retain(|dir, mode| {
+            if dirs.remove(dir) == Some(*mode) {
+                return true;
+            }
+
+            debug!("Stop watching {dir:?}");
+            if let Err(e) = self.watcher.unwatch(dir) {
+                warn!("Failed to stop watching {dir:?}: {e}");
+            }
+            false
+        });
+
+        for (dir, mode) in dirs {
+            debug!("Watching {dir:?} ...");
+            match self.watcher.watch(&dir, mode) {
+                Ok(()) => {
+                    self.watched.insert(dir, mode);
+                }
+                Err(e) => warn!("Failed to watch {dir:?}: {e}"),
+            }
+        }
+    }
+}
+
+/// Files which may have new content after `event`, e.g. written, created, or renamed to.
+pub fn changed_files(event: &Event) -> &[PathBuf] {
+    match event.kind {
+        EventKind::Create(_)
+        | EventKind::Modify(ModifyKind::Data(_) | ModifyKind::Any)
+        | EventKind::Modify(ModifyKind::Name(RenameMode::To | RenameMode::Any))
+        | EventKind::Access(AccessKind::Close(AccessMode::Write)) => &event.paths,
+        // The paths are where the file is renamed from, and where to.
+        EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {
+            event.paths.last().map_or(&[], std::slice::from_ref)
+        }
+        _ => &[],
+    }
+}
+
+/// Whether `path` is in `dir`, or in its subdirectories if `recursive`.
+pub fn is_in(path: &Path, dir: &Path, recursive: bool) -> bool {
+    if recursive {
+        path.starts_with(dir)
+    } else {
+        path.parent() == Some(dir)
+    }
+}